@@ -0,0 +1,329 @@
+use std::{
+    fs,
+    io::{Cursor, Read},
+    path::Path,
+};
+
+use axum::{
+    body::{Body, Bytes},
+    extract::{Path as RoutePath, State},
+    http::{header, HeaderMap, Response, StatusCode},
+    response::IntoResponse,
+};
+use image::ImageReader;
+
+use crate::{
+    banner::{Theme, ThemeManager},
+    manifest::{SpriteSheet, ThemeManifest, MANIFEST_FILE_NAME},
+    report::ThemeReport,
+    vector::{self, VectorImage},
+    SharedState,
+};
+
+// uploaded glyphs larger than this on either side are refused
+const MAX_GLYPH_SIZE: u32 = 1024;
+const MAX_THEME_FILES: usize = 64;
+
+fn reply(status: StatusCode, msg: &str) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .body(Body::from(msg.to_string()))
+        .unwrap()
+}
+
+fn invalid(msg: String) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, msg)
+}
+
+/// Whether the request carries `Authorization: Bearer <admin_token>`, never
+/// when no token is configured.
+fn authorized(headers: &HeaderMap, admin_token: &str) -> bool {
+    if admin_token.is_empty() {
+        return false;
+    }
+    let token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+
+    match token {
+        // compare every byte so the time taken tells nothing
+        Some(token) => {
+            token.len() == admin_token.len()
+                && token
+                    .bytes()
+                    .zip(admin_token.bytes())
+                    .fold(0, |diff, (a, b)| diff | (a ^ b))
+                    == 0
+        }
+        None => false,
+    }
+}
+
+fn is_valid_name(theme_name: &str) -> bool {
+    !theme_name.is_empty()
+        && theme_name.len() <= 64
+        && theme_name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Files of an uploaded theme, flattened to their file names.
+struct ThemeFiles {
+    files: Vec<(String, Vec<u8>)>,
+    // bytes left before the theme is too large
+    budget: u64,
+}
+
+impl ThemeFiles {
+    fn add(&mut self, path: &Path, reader: impl Read) -> std::io::Result<()> {
+        let file_name = match path.file_name() {
+            Some(file_name) => file_name.to_string_lossy().to_string(),
+            None => return Ok(()),
+        };
+        // skip what archivers leave behind, like .DS_Store and __MACOSX
+        if file_name.starts_with('.')
+            || path
+                .components()
+                .any(|component| component.as_os_str() == "__MACOSX")
+        {
+            return Ok(());
+        }
+        if self.files.iter().any(|(name, _)| *name == file_name) {
+            return Err(invalid(format!("file given twice: {file_name}")));
+        }
+        if self.files.len() >= MAX_THEME_FILES {
+            return Err(invalid(format!("more than {MAX_THEME_FILES} files")));
+        }
+
+        let mut bytes = Vec::new();
+        reader.take(self.budget + 1).read_to_end(&mut bytes)?;
+        if bytes.len() as u64 > self.budget {
+            return Err(invalid("theme is too large".to_string()));
+        }
+        self.budget -= bytes.len() as u64;
+
+        self.files.push((file_name, bytes));
+        Ok(())
+    }
+
+    fn read_zip(&mut self, bytes: &[u8]) -> std::io::Result<()> {
+        let mut archive = zip::ZipArchive::new(Cursor::new(bytes))?;
+        for idx in 0..archive.len() {
+            let file = archive.by_index(idx)?;
+            if !file.is_file() {
+                continue;
+            }
+            let path = file
+                .enclosed_name()
+                .ok_or_else(|| invalid(format!("bad path: {}", file.name())))?;
+            self.add(&path, file)?;
+        }
+        Ok(())
+    }
+
+    fn read_tar(&mut self, reader: impl Read) -> std::io::Result<()> {
+        let mut archive = tar::Archive::new(reader);
+        for entry in archive.entries()? {
+            let entry = entry?;
+            if !entry.header().entry_type().is_file() {
+                continue;
+            }
+            let path = entry.path()?.into_owned();
+            self.add(&path, entry)?;
+        }
+        Ok(())
+    }
+}
+
+/// Unpack a zip, tar or gzipped tar archive.
+fn read_archive(bytes: &[u8], max_size: u64) -> std::io::Result<Vec<(String, Vec<u8>)>> {
+    let mut theme_files = ThemeFiles {
+        files: Vec::new(),
+        budget: max_size,
+    };
+    if bytes.starts_with(b"PK\x03\x04") {
+        theme_files.read_zip(bytes)?;
+    } else if bytes.starts_with(&[0x1f, 0x8b]) {
+        theme_files.read_tar(flate2::read::GzDecoder::new(bytes))?;
+    } else {
+        theme_files.read_tar(bytes)?;
+    }
+    Ok(theme_files.files)
+}
+
+/// Refuse glyphs larger than `MAX_GLYPH_SIZE`, for a sprite sheet the
+/// cells cut out of it.
+fn check_size(
+    file_name: &str,
+    (width, height): (u32, u32),
+    sprite: Option<&SpriteSheet>,
+) -> std::io::Result<()> {
+    let too_large = match sprite {
+        Some(sprite) if sprite.file == file_name => {
+            let (cell_width, cell_height) = sprite.cell_size(width, height);
+            // a sheet holds no more cells than it has glyphs
+            let max_sheet_size = MAX_GLYPH_SIZE.saturating_mul(sprite.glyphs.len() as u32);
+            cell_width > MAX_GLYPH_SIZE
+                || cell_height > MAX_GLYPH_SIZE
+                || width > max_sheet_size
+                || height > max_sheet_size
+        }
+        _ => width > MAX_GLYPH_SIZE || height > MAX_GLYPH_SIZE,
+    };
+    if too_large {
+        return Err(invalid(format!(
+            "{file_name} has glyphs larger than {MAX_GLYPH_SIZE}x{MAX_GLYPH_SIZE}"
+        )));
+    }
+    Ok(())
+}
+
+/// Check the glyph sizes before decoding anything, then build the theme.
+fn validate(
+    theme_name: &str,
+    files: &[(String, Vec<u8>)],
+) -> std::io::Result<(Theme, ThemeReport)> {
+    // a broken manifest is left for Theme::from_files to report
+    let sprite = files
+        .iter()
+        .find(|(file_name, _)| file_name == MANIFEST_FILE_NAME)
+        .and_then(|(_, bytes)| ThemeManifest::from_bytes(bytes).ok())
+        .and_then(|manifest| manifest.sprite);
+
+    for (file_name, bytes) in files {
+        if vector::is_svg(bytes) {
+            let vector = VectorImage::from_bytes(bytes)
+                .map_err(|e| invalid(format!("bad svg {file_name}: {e}")))?;
+            check_size(
+                file_name,
+                (vector.width(), vector.height()),
+                sprite.as_ref(),
+            )?;
+            continue;
+        }
+        let reader = match ImageReader::new(Cursor::new(bytes)).with_guessed_format() {
+            Ok(reader) if reader.format().is_some() => reader,
+            // not an image, left for Theme::from_files to report
+            _ => continue,
+        };
+        let size = reader
+            .into_dimensions()
+            .map_err(|e| invalid(format!("bad image {file_name}: {e}")))?;
+        check_size(file_name, size, sprite.as_ref())?;
+    }
+    Theme::from_files(theme_name, files)
+}
+
+/// Write the theme next to the others, replacing the old one if any.
+fn persist(themes_dir: &str, theme_name: &str, files: &[(String, Vec<u8>)]) -> std::io::Result<()> {
+    let themes_dir = Path::new(themes_dir);
+    // hidden from the theme loader until complete
+    let staging_dir = themes_dir.join(format!(".{theme_name}.upload"));
+    let _ = fs::remove_dir_all(&staging_dir);
+    fs::create_dir_all(&staging_dir)?;
+    for (file_name, bytes) in files {
+        fs::write(staging_dir.join(file_name), bytes)?;
+    }
+
+    let theme_dir = themes_dir.join(theme_name);
+    if theme_dir.exists() {
+        fs::remove_dir_all(&theme_dir)?;
+    }
+    fs::rename(&staging_dir, &theme_dir)
+}
+
+/// `PUT /admin/themes/:name`, the body is the theme folder as a zip, tar or
+/// gzipped tar archive.
+pub async fn upload_theme(
+    RoutePath(theme_name): RoutePath<String>,
+    State(app_state): State<SharedState>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> impl IntoResponse {
+    let config = app_state.config.clone();
+    if config.admin_token.is_empty() {
+        return reply(StatusCode::NOT_FOUND, "admin api is disabled");
+    }
+    if !authorized(&headers, &config.admin_token) {
+        return reply(StatusCode::UNAUTHORIZED, "bad admin token");
+    }
+    if !is_valid_name(&theme_name) {
+        return reply(StatusCode::BAD_REQUEST, "bad theme name");
+    }
+
+    let name = theme_name.clone();
+    let uploaded = tokio::task::spawn_blocking(move || {
+        let files = read_archive(&body, config.max_theme_size as u64)?;
+        let uploaded = validate(&name, &files)?;
+        persist(&config.themes_dir, &name, &files)
+            .map_err(|e| std::io::Error::other(format!("failed to save theme: {e}")))?;
+        Ok::<_, std::io::Error>(uploaded)
+    })
+    .await;
+
+    match uploaded {
+        Ok(Ok((theme, report))) => {
+            app_state.update_theme_manager(|theme_manager| {
+                theme_manager.insert(&theme_name, theme, report)
+            });
+            println!("[PUT] /admin/themes/{} | theme uploaded", theme_name);
+            reply(StatusCode::CREATED, "theme uploaded")
+        }
+        Ok(Err(e)) if e.kind() == std::io::ErrorKind::InvalidData => {
+            reply(StatusCode::BAD_REQUEST, &e.to_string())
+        }
+        Ok(Err(e)) => reply(StatusCode::INTERNAL_SERVER_ERROR, &e.to_string()),
+        Err(_) => reply(StatusCode::INTERNAL_SERVER_ERROR, "failed to upload theme"),
+    }
+}
+
+/// `DELETE /admin/themes/:name`, only themes in `themes_dir` can be deleted.
+/// An embedded theme of the same name takes over again.
+pub async fn delete_theme(
+    RoutePath(theme_name): RoutePath<String>,
+    State(app_state): State<SharedState>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    let config = app_state.config.clone();
+    if config.admin_token.is_empty() {
+        return reply(StatusCode::NOT_FOUND, "admin api is disabled");
+    }
+    if !authorized(&headers, &config.admin_token) {
+        return reply(StatusCode::UNAUTHORIZED, "bad admin token");
+    }
+    if !is_valid_name(&theme_name) {
+        return reply(StatusCode::BAD_REQUEST, "bad theme name");
+    }
+
+    let theme_dir = Path::new(&config.themes_dir).join(&theme_name);
+    if !theme_dir.is_dir() {
+        return reply(StatusCode::NOT_FOUND, "no such theme in themes_dir");
+    }
+
+    let name = theme_name.clone();
+    let deleted = tokio::task::spawn_blocking(move || {
+        fs::remove_dir_all(&theme_dir)?;
+        // the embedded theme the deleted one overrode is used again
+        Ok::<_, std::io::Error>(ThemeManager::load_embedded(&name))
+    })
+    .await;
+
+    match deleted {
+        Ok(Ok(embedded)) => {
+            app_state.update_theme_manager(|theme_manager| match embedded {
+                Some((theme, report)) => theme_manager.insert(&theme_name, theme, report),
+                None => {
+                    theme_manager.remove(&theme_name);
+                }
+            });
+            println!("[DELETE] /admin/themes/{} | theme deleted", theme_name);
+            reply(StatusCode::NO_CONTENT, "")
+        }
+        Ok(Err(e)) => reply(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            &format!("failed to delete theme: {e}"),
+        ),
+        Err(_) => reply(StatusCode::INTERNAL_SERVER_ERROR, "failed to delete theme"),
+    }
+}
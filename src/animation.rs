@@ -0,0 +1,372 @@
+use image::{
+    codecs::{
+        gif::{GifEncoder, Repeat},
+        webp::WebPEncoder,
+    },
+    Delay, Frame, ImageFormat, RgbaImage,
+};
+
+use std::{
+    error::Error,
+    io::{Cursor, Write},
+};
+
+use crate::utils;
+
+// browsers play a zero delay frame at 100ms, so do we
+const DEFAULT_FRAME_DELAY_MS: u32 = 100;
+// longest loop we are willing to build so that every track lines up
+const MAX_LOOP_MS: u32 = 10_000;
+
+#[derive(Debug, Clone)]
+pub struct AnimationFrame {
+    pub buffer: RgbaImage,
+    pub delay_ms: u32,
+}
+
+impl From<Frame> for AnimationFrame {
+    fn from(value: Frame) -> Self {
+        let (numer, denom) = value.delay().numer_denom_ms();
+        let delay_ms = match numer / denom.max(1) {
+            0 => DEFAULT_FRAME_DELAY_MS,
+            delay => delay,
+        };
+
+        AnimationFrame {
+            buffer: value.into_buffer(),
+            delay_ms,
+        }
+    }
+}
+
+/// One composited frame of the merged timeline, with the frame index
+/// each track shows during it.
+#[derive(Debug, Clone)]
+pub struct TimelineStep {
+    pub delay_ms: u32,
+    pub frame_indices: Vec<usize>,
+}
+
+fn frame_at(delays: &[u32], time: u32) -> usize {
+    let total: u32 = delays.iter().sum();
+    let mut time = time % total;
+    for (idx, delay) in delays.iter().enumerate() {
+        if time < *delay {
+            return idx;
+        }
+        time -= delay;
+    }
+    delays.len() - 1
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+/// The length of a loop in which every track ends exactly on its own
+/// loop boundary, or the longest track if such a loop would be too long.
+fn loop_period(durations: &[u32]) -> u32 {
+    let longest = durations.iter().copied().max().unwrap_or(0);
+
+    let mut period: u64 = 1;
+    for duration in durations.iter().filter(|duration| **duration > 0) {
+        let duration = *duration as u64;
+        period = period / gcd(period, duration) * duration;
+        if period > MAX_LOOP_MS as u64 {
+            return longest;
+        }
+    }
+
+    if longest == 0 {
+        0
+    } else {
+        period as u32
+    }
+}
+
+/// Merge the frame delays of several tracks into a single timeline.
+///
+/// A track with no delays is a still image and always shows frame 0.
+/// The timeline runs until all tracks finish their loop together, so the
+/// composite loops cleanly, shorter tracks repeat within it. When that
+/// would take longer than `MAX_LOOP_MS`, the timeline is only as long as
+/// the longest track, and the other tracks jump back to their first frame
+/// whenever it loops.
+pub fn merge_timeline(tracks: &[Vec<u32>]) -> Vec<TimelineStep> {
+    let durations: Vec<u32> = tracks
+        .iter()
+        .map(|delays| delays.iter().sum::<u32>())
+        .collect();
+    let period = loop_period(&durations);
+
+    if period == 0 {
+        return vec![TimelineStep {
+            delay_ms: DEFAULT_FRAME_DELAY_MS,
+            frame_indices: vec![0; tracks.len()],
+        }];
+    }
+
+    // collect every point in time where any track changes its frame
+    let mut change_points = vec![0, period];
+    for delays in tracks.iter().filter(|delays| !delays.is_empty()) {
+        let mut time = 0;
+        for delay in delays.iter().cycle() {
+            time += delay;
+            if time >= period {
+                break;
+            }
+            change_points.push(time);
+        }
+    }
+    change_points.sort_unstable();
+    change_points.dedup();
+
+    change_points
+        .windows(2)
+        .map(|window| TimelineStep {
+            delay_ms: window[1] - window[0],
+            frame_indices: tracks
+                .iter()
+                .map(|delays| {
+                    if delays.is_empty() {
+                        0
+                    } else {
+                        frame_at(delays, window[0])
+                    }
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone)]
+pub struct AnimatedImage {
+    frames: Vec<AnimationFrame>,
+    format: ImageFormat,
+}
+
+impl AnimatedImage {
+    pub fn new(format: ImageFormat) -> Self {
+        AnimatedImage {
+            frames: Vec::new(),
+            format,
+        }
+    }
+
+    pub fn push_frame(&mut self, buffer: RgbaImage, delay_ms: u32) {
+        self.frames.push(AnimationFrame { buffer, delay_ms });
+    }
+
+    /// Encode all frames, `quality` makes WebP frames lossy and is
+    /// ignored by the other formats.
+    pub fn encode(&self, quality: Option<u8>) -> Result<Vec<u8>, Box<dyn Error>> {
+        let mut buffer = Cursor::new(Vec::new());
+
+        match self.format {
+            ImageFormat::Gif => {
+                // speed 1 is painfully slow for dozens of frames
+                let mut encoder = GifEncoder::new_with_speed(&mut buffer, 10);
+                encoder.set_repeat(Repeat::Infinite)?;
+                encoder.encode_frames(self.frames.iter().map(|frame| {
+                    let delay = Delay::from_numer_denom_ms(frame.delay_ms, 1);
+                    Frame::from_parts(frame.buffer.clone(), 0, 0, delay)
+                }))?;
+            }
+            ImageFormat::WebP => self.encode_webp(&mut buffer, quality)?,
+            ImageFormat::Png => self.encode_apng(&mut buffer)?,
+            _ => {
+                return Err(Box::new(std::io::Error::new(
+                    std::io::ErrorKind::Unsupported,
+                    "unsupported animation format",
+                )))
+            }
+        }
+
+        Ok(buffer.into_inner())
+    }
+
+    fn size(&self) -> (u32, u32) {
+        self.frames
+            .first()
+            .map(|frame| frame.buffer.dimensions())
+            .unwrap_or((1, 1))
+    }
+
+    // neither image-webp nor libwebp's simple api write animations, so we
+    // encode each frame on its own and assemble the container around them
+    fn encode_webp<W: Write>(&self, w: &mut W, quality: Option<u8>) -> Result<(), Box<dyn Error>> {
+        let (width, height) = self.size();
+
+        let mut chunks = Vec::new();
+
+        let mut vp8x = Vec::new();
+        vp8x.push(0b0001_0010); // alpha and animation flags
+        vp8x.extend_from_slice(&[0; 3]);
+        vp8x.extend_from_slice(&(width - 1).to_le_bytes()[..3]);
+        vp8x.extend_from_slice(&(height - 1).to_le_bytes()[..3]);
+        write_riff_chunk(&mut chunks, b"VP8X", &vp8x)?;
+
+        let mut anim = Vec::new();
+        anim.extend_from_slice(&[0; 4]); // transparent background
+        anim.extend_from_slice(&0u16.to_le_bytes()); // loop forever
+        write_riff_chunk(&mut chunks, b"ANIM", &anim)?;
+
+        for frame in self.frames.iter() {
+            let still = match quality {
+                Some(quality) => utils::encode_webp_lossy(&frame.buffer, quality)?,
+                None => {
+                    let mut still = Vec::new();
+                    frame
+                        .buffer
+                        .write_with_encoder(WebPEncoder::new_lossless(&mut still))?;
+                    still
+                }
+            };
+
+            let mut anmf = Vec::new();
+            anmf.extend_from_slice(&[0; 6]); // frame offset
+            anmf.extend_from_slice(&(width - 1).to_le_bytes()[..3]);
+            anmf.extend_from_slice(&(height - 1).to_le_bytes()[..3]);
+            anmf.extend_from_slice(&frame.delay_ms.min(0xff_ffff).to_le_bytes()[..3]);
+            anmf.push(0b0000_0010); // replace the canvas instead of blending
+
+            // keep the ALPH, VP8 or VP8L chunks of the still image, its own
+            // RIFF header and VP8X chunk are replaced by ours
+            let mut still_chunks = &still[12..];
+            if still_chunks.starts_with(b"VP8X") {
+                still_chunks = &still_chunks[18..];
+            }
+            anmf.extend_from_slice(still_chunks);
+            write_riff_chunk(&mut chunks, b"ANMF", &anmf)?;
+        }
+
+        w.write_all(b"RIFF")?;
+        w.write_all(&(chunks.len() as u32 + 4).to_le_bytes())?;
+        w.write_all(b"WEBP")?;
+        w.write_all(&chunks)?;
+
+        Ok(())
+    }
+
+    fn encode_apng<W: Write>(&self, w: &mut W) -> Result<(), Box<dyn Error>> {
+        let (width, height) = self.size();
+
+        let mut encoder = png::Encoder::new(w, width, height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder.set_animated(self.frames.len() as u32, 0)?;
+
+        let mut writer = encoder.write_header()?;
+        for frame in self.frames.iter() {
+            writer.set_frame_delay(frame.delay_ms.min(u16::MAX as u32) as u16, 1000)?;
+            writer.set_blend_op(png::BlendOp::Source)?;
+            writer.write_image_data(frame.buffer.as_raw())?;
+        }
+        writer.finish()?;
+
+        Ok(())
+    }
+
+    pub fn format(&self) -> ImageFormat {
+        self.format
+    }
+}
+
+fn write_riff_chunk<W: Write>(w: &mut W, name: &[u8; 4], data: &[u8]) -> std::io::Result<()> {
+    w.write_all(name)?;
+    w.write_all(&(data.len() as u32).to_le_bytes())?;
+    w.write_all(data)?;
+    if data.len() % 2 == 1 {
+        w.write_all(&[0])?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{codecs::webp::WebPDecoder, AnimationDecoder, Rgba};
+
+    use super::*;
+
+    fn delays(timeline: &[TimelineStep]) -> Vec<u32> {
+        timeline.iter().map(|step| step.delay_ms).collect()
+    }
+
+    fn frame_indices(timeline: &[TimelineStep]) -> Vec<Vec<usize>> {
+        timeline
+            .iter()
+            .map(|step| step.frame_indices.clone())
+            .collect()
+    }
+
+    #[test]
+    fn merge_equal_durations() {
+        let timeline = merge_timeline(&[vec![100, 200], vec![100, 200]]);
+        assert_eq!(delays(&timeline), [100, 200]);
+        assert_eq!(frame_indices(&timeline), [vec![0, 0], vec![1, 1]]);
+    }
+
+    #[test]
+    fn merge_coprime_durations() {
+        // 300ms and 200ms line up after 600ms
+        let timeline = merge_timeline(&[vec![100, 200], vec![100, 100], vec![]]);
+        assert_eq!(delays(&timeline), [100; 6]);
+        assert_eq!(
+            frame_indices(&timeline),
+            [
+                vec![0, 0, 0],
+                vec![1, 1, 0],
+                vec![1, 0, 0],
+                vec![0, 1, 0],
+                vec![1, 0, 0],
+                vec![1, 1, 0],
+            ]
+        );
+    }
+
+    #[test]
+    fn merge_capped_durations() {
+        // 5001ms and 5003ms would only line up after 25s
+        let timeline = merge_timeline(&[vec![5000, 1], vec![5000, 3]]);
+        assert_eq!(delays(&timeline).iter().sum::<u32>(), 5003);
+        assert_eq!(delays(&timeline), [5000, 1, 2]);
+        assert_eq!(
+            frame_indices(&timeline),
+            [vec![0, 0], vec![1, 1], vec![0, 1]]
+        );
+    }
+
+    #[test]
+    fn merge_still_images() {
+        let timeline = merge_timeline(&[vec![], vec![]]);
+        assert_eq!(delays(&timeline), [DEFAULT_FRAME_DELAY_MS]);
+        assert_eq!(frame_indices(&timeline), [vec![0, 0]]);
+    }
+
+    #[test]
+    fn webp_round_trip() {
+        for quality in [None, Some(80)] {
+            let mut animation = AnimatedImage::new(ImageFormat::WebP);
+            for (color, delay_ms) in [([255, 0, 0, 255], 100), ([0, 0, 255, 128], 250)] {
+                animation.push_frame(RgbaImage::from_pixel(4, 3, Rgba(color)), delay_ms);
+            }
+            let bytes = animation.encode(quality).unwrap();
+
+            let decoder = WebPDecoder::new(Cursor::new(bytes)).unwrap();
+            let frames = decoder.into_frames().collect_frames().unwrap();
+            assert_eq!(frames.len(), 2);
+            let delays: Vec<(u32, u32)> = frames
+                .iter()
+                .map(|frame| frame.delay().numer_denom_ms())
+                .collect();
+            assert_eq!(delays, [(100, 1), (250, 1)]);
+            assert!(frames
+                .iter()
+                .all(|frame| frame.buffer().dimensions() == (4, 3)));
+        }
+    }
+}
@@ -0,0 +1,71 @@
+use image::{Rgba, RgbaImage};
+use resvg::{tiny_skia, usvg};
+
+use crate::utils;
+
+/// Box drawn behind the glyphs of a counter.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Backdrop {
+    // pixels between the glyphs and the edge of the image
+    pub padding: u32,
+    pub background: Option<Rgba<u8>>,
+    pub border: Option<Rgba<u8>>,
+    pub border_width: u32,
+    // corner radius of both background and border
+    pub radius: u32,
+}
+
+impl Backdrop {
+    pub fn is_visible(&self) -> bool {
+        self.background.is_some() || (self.border.is_some() && self.border_width > 0)
+    }
+
+    /// The `<rect>` for an image of the given size, empty if there is
+    /// nothing to draw. The border is stroked inside the image.
+    pub fn svg_rect(&self, width: u32, height: u32) -> String {
+        if !self.is_visible() {
+            return String::new();
+        }
+
+        let border_width = match self.border {
+            Some(_) => self.border_width.min(width / 2).min(height / 2),
+            None => 0,
+        };
+        let inset = border_width as f32 / 2.0;
+        let rect_width = width - border_width;
+        let rect_height = height - border_width;
+        let radius = self.radius.min(rect_width / 2).min(rect_height / 2);
+
+        let fill = match self.background {
+            Some(color) => utils::svg_paint("fill", color),
+            None => " fill=\"none\"".to_string(),
+        };
+        let stroke = match self.border {
+            Some(color) if border_width > 0 => {
+                utils::svg_paint("stroke", color) + &format!(" stroke-width=\"{border_width}\"")
+            }
+            _ => String::new(),
+        };
+
+        format!("<rect x=\"{inset}\" y=\"{inset}\" width=\"{rect_width}\" height=\"{rect_height}\" rx=\"{radius}\"{fill}{stroke} />\n")
+    }
+
+    /// Rasterize the backdrop as the canvas the glyphs are drawn on,
+    /// `None` if there is nothing to draw.
+    pub fn render(&self, width: u32, height: u32) -> Option<RgbaImage> {
+        if !self.is_visible() {
+            return None;
+        }
+
+        let svg = format!(
+            "<svg width=\"{width}\" height=\"{height}\" xmlns=\"http://www.w3.org/2000/svg\">{}</svg>",
+            self.svg_rect(width, height)
+        );
+        let tree = usvg::Tree::from_str(&svg, &usvg::Options::default()).ok()?;
+
+        let mut pixmap = tiny_skia::Pixmap::new(width, height)?;
+        resvg::render(&tree, tiny_skia::Transform::default(), &mut pixmap.as_mut());
+
+        Some(utils::pixmap_to_rgba(&pixmap))
+    }
+}
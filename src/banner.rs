@@ -1,21 +1,49 @@
+use axum::body::Bytes;
 use base64::{engine::general_purpose::STANDARD as base64_encoder, Engine};
-use image::{DynamicImage, GenericImage, ImageFormat, ImageReader, ImageResult, RgbaImage};
+use image::{
+    codecs::{
+        avif::AvifEncoder, gif::GifDecoder, jpeg::JpegEncoder, png::PngDecoder, webp::WebPDecoder,
+    },
+    imageops::{self, FilterType},
+    AnimationDecoder, DynamicImage, ImageFormat, ImageReader, ImageResult, Rgba, RgbaImage,
+};
+use rand::seq::SliceRandom;
+use resvg::{tiny_skia, usvg};
+use serde::Serialize;
 
 use std::{
+    borrow::Cow,
     collections::HashMap,
     error::Error,
     fmt,
     io::{Cursor, Seek, Write},
     ops::Deref,
     path::Path,
+    sync::Arc,
 };
 
-use crate::utils;
+use crate::{
+    animation::{self, AnimatedImage, AnimationFrame},
+    backdrop::Backdrop,
+    cache,
+    color::ColorTransform,
+    fallback, font,
+    manifest::{Align, FontGlyphs, SpriteSheet, ThemeManifest, MANIFEST_FILE_NAME},
+    notation::{self, Notation, NumberMarks},
+    report::ThemeReport,
+    utils,
+    vector::{self, VectorImage},
+};
 
 #[derive(Debug, Clone)]
 pub struct DynamicImageWithFormat {
     data: DynamicImage,
     format: image::ImageFormat,
+    // all frames of an animated image, empty for a still one
+    frames: Vec<AnimationFrame>,
+    // source of a glyph drawn from svg, `data` holds it rasterized at its
+    // own size
+    vector: Option<VectorImage>,
 }
 
 impl Deref for DynamicImageWithFormat {
@@ -29,29 +57,118 @@ impl Deref for DynamicImageWithFormat {
 impl TryFrom<rust_embed::EmbeddedFile> for DynamicImageWithFormat {
     type Error = Box<dyn Error>;
     fn try_from(value: rust_embed::EmbeddedFile) -> Result<Self, Self::Error> {
-        let bytes = Cursor::new(value.data.as_ref());
-        let reader = ImageReader::new(bytes).with_guessed_format()?;
+        Self::from_bytes(value.data.as_ref())
+    }
+}
+
+impl DynamicImageWithFormat {
+    pub fn new(data: DynamicImage, format: ImageFormat) -> Self {
+        DynamicImageWithFormat {
+            data,
+            format,
+            frames: Vec::new(),
+            vector: None,
+        }
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Box<dyn Error>> {
+        if vector::is_svg(bytes) {
+            return Self::from_vector(VectorImage::from_bytes(bytes)?);
+        }
+
+        // get format
+        let reader = ImageReader::new(Cursor::new(bytes)).with_guessed_format()?;
         let format = reader.format().ok_or(std::io::Error::new(
             std::io::ErrorKind::InvalidData,
             "unable to detect image format",
         ))?;
         let data = reader.decode()?;
+        let frames = Self::decode_frames(bytes, format)?;
 
-        Ok(DynamicImageWithFormat { data, format })
+        Ok(DynamicImageWithFormat {
+            data,
+            format,
+            frames,
+            vector: None,
+        })
     }
-}
 
-impl DynamicImageWithFormat {
-    pub fn open<P>(path: P) -> ImageResult<Self>
-    where
-        P: AsRef<std::path::Path>,
-    {
-        // get format
-        let reader = ImageReader::open(path)?.with_guessed_format()?;
-        let format = reader.format().unwrap();
-        let data = reader.decode()?;
+    pub fn from_vector(vector: VectorImage) -> Result<Self, Box<dyn Error>> {
+        let data = vector.rasterize(1.0).ok_or(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "unable to rasterize svg",
+        ))?;
+        Ok(DynamicImageWithFormat {
+            data: DynamicImage::ImageRgba8(data),
+            format: ImageFormat::Png,
+            frames: Vec::new(),
+            vector: Some(vector),
+        })
+    }
+
+    /// Cut out a part of the image, of every frame if animated.
+    fn crop(&self, x: u32, y: u32, width: u32, height: u32) -> Self {
+        DynamicImageWithFormat {
+            data: self.data.crop_imm(x, y, width, height),
+            format: self.format,
+            frames: self
+                .frames
+                .iter()
+                .map(|frame| AnimationFrame {
+                    buffer: imageops::crop_imm(&frame.buffer, x, y, width, height).to_image(),
+                    delay_ms: frame.delay_ms,
+                })
+                .collect(),
+            // cut out of a vector sheet, only the rasterized cell is left
+            vector: None,
+        }
+    }
+
+    fn decode_frames(bytes: &[u8], format: ImageFormat) -> ImageResult<Vec<AnimationFrame>> {
+        let frames = match format {
+            ImageFormat::Gif => GifDecoder::new(Cursor::new(bytes))?
+                .into_frames()
+                .collect_frames()?,
+            ImageFormat::WebP => {
+                let decoder = WebPDecoder::new(Cursor::new(bytes))?;
+                if !decoder.has_animation() {
+                    return Ok(Vec::new());
+                }
+                decoder.into_frames().collect_frames()?
+            }
+            ImageFormat::Png => {
+                let decoder = PngDecoder::new(Cursor::new(bytes))?;
+                if !decoder.is_apng()? {
+                    return Ok(Vec::new());
+                }
+                decoder.apng()?.into_frames().collect_frames()?
+            }
+            _ => Vec::new(),
+        };
+
+        // a single frame is just a still image
+        if frames.len() < 2 {
+            return Ok(Vec::new());
+        }
+        Ok(frames.into_iter().map(AnimationFrame::from).collect())
+    }
+
+    pub fn frame_delays(&self) -> Vec<u32> {
+        self.frames.iter().map(|frame| frame.delay_ms).collect()
+    }
+
+    fn frame(&self, idx: usize) -> RgbaImage {
+        match self.frames.get(idx) {
+            Some(frame) => frame.buffer.clone(),
+            None => self.data.to_rgba8(),
+        }
+    }
 
-        Ok(DynamicImageWithFormat { data, format })
+    /// A frame recoloured, see `frame`.
+    fn colored_frame(&self, idx: usize, color: &ColorTransform) -> RgbaImage {
+        let mut frame = self.frame(idx);
+        color.apply(&mut frame);
+        frame
     }
 
     pub fn as_raw(&self) -> &DynamicImage {
@@ -65,16 +182,60 @@ impl DynamicImageWithFormat {
         self.data.write_to(w, self.format)
     }
 
-    pub fn encode(&self) -> Result<Vec<u8>, Box<dyn Error>> {
+    /// Encode the image, `quality` only applies to lossy formats.
+    /// WebP without a quality is encoded lossless.
+    pub fn encode(&self, quality: Option<u8>) -> Result<Vec<u8>, Box<dyn Error>> {
         let mut buffer = Cursor::new(Vec::new());
 
-        self.data.write_to(&mut buffer, self.format)?;
+        match (self.format, quality) {
+            (ImageFormat::WebP, Some(quality)) => {
+                return Ok(utils::encode_webp_lossy(&self.data.to_rgba8(), quality)?);
+            }
+            (ImageFormat::Jpeg, quality) => {
+                let quality = quality.unwrap_or(utils::DEFAULT_QUALITY);
+                utils::flatten_alpha(&self.data.to_rgba8())
+                    .write_with_encoder(JpegEncoder::new_with_quality(&mut buffer, quality))?;
+            }
+            (ImageFormat::Avif, quality) => {
+                let quality = quality.unwrap_or(utils::DEFAULT_QUALITY);
+                // the slower speeds take seconds even for a small counter
+                self.data
+                    .write_with_encoder(AvifEncoder::new_with_speed_quality(
+                        &mut buffer,
+                        10,
+                        quality,
+                    ))?;
+            }
+            _ => self.data.write_to(&mut buffer, self.format)?,
+        }
 
         Ok(buffer.into_inner())
     }
 
     pub fn format(&self) -> ImageFormat {
-        self.format.clone()
+        self.format
+    }
+
+    /// Extension of the format the image was read from, `svg` included.
+    pub fn format_name(&self) -> &'static str {
+        match self.vector {
+            Some(_) => "svg",
+            None => self.format.extensions_str()[0],
+        }
+    }
+
+    pub fn vector(&self) -> Option<&VectorImage> {
+        self.vector.as_ref()
+    }
+
+    /// Mime type and bytes of the image as linked or embedded in svg.
+    fn source(&self) -> (&'static str, Vec<u8>) {
+        if let Some(vector) = &self.vector {
+            return ("image/svg+xml", vector.to_svg("").into_bytes());
+        }
+        let mut buffer = Cursor::new(Vec::new());
+        self.write_to(&mut buffer).unwrap();
+        (self.format.to_mime_type(), buffer.into_inner())
     }
 }
 
@@ -89,118 +250,895 @@ impl SvgImage {
     pub fn data(&self) -> &str {
         &self.data
     }
-}
 
-impl From<&DynamicImageWithFormat> for SvgImage {
-    fn from(value: &DynamicImageWithFormat) -> Self {
-        let mut buffer = Cursor::new(Vec::new());
-        value.write_to(&mut buffer).unwrap();
+    pub fn rasterize(&self, pixelated: bool) -> Result<RgbaImage, Box<dyn Error>> {
+        // resvg does not understand the css `pixelated` keyword
+        let mut options = usvg::Options::default();
+        if pixelated {
+            options.image_rendering = usvg::ImageRendering::OptimizeSpeed;
+        }
+        let tree = usvg::Tree::from_str(&self.data, &options)?;
+
+        let mut pixmap = tiny_skia::Pixmap::new(self.width, self.height).ok_or(
+            std::io::Error::new(std::io::ErrorKind::InvalidInput, "empty svg image"),
+        )?;
+        resvg::render(&tree, tiny_skia::Transform::default(), &mut pixmap.as_mut());
 
-        let data = buffer.into_inner();
+        Ok(utils::pixmap_to_rgba(&pixmap))
+    }
+}
+
+impl SvgImage {
+    /// An image as a data uri, from the bytes `source` gave.
+    fn embed(image: &DynamicImageWithFormat, mime_type: &str, data: &[u8]) -> Self {
         let encoded_data = base64_encoder.encode(data);
 
-        let data = format!(
-            "data:{};charset=utf-8;base64,{}",
-            value.format.to_mime_type(),
-            encoded_data
-        );
+        let data = format!("data:{};charset=utf-8;base64,{}", mime_type, encoded_data);
 
         SvgImage {
-            width: value.width(),
-            height: value.height(),
+            width: image.width(),
+            height: image.height(),
             data,
         }
     }
 }
 
+/// A single image of a theme, a digit or one of the optional extras.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Glyph {
+    Digit(u32),
+    // thousands separator
+    Comma,
+    // decimal point
+    Dot,
+    // thousands separator of some locales
+    Space,
+    // units of compact numbers
+    Thousand,
+    Million,
+    Billion,
+    Trillion,
+    // decorative end caps
+    Prefix,
+    Suffix,
+}
+
+impl Glyph {
+    /// File name of the glyph without extension, the opposite of `from_name`.
+    pub fn name(&self) -> String {
+        match self {
+            Glyph::Digit(digit) => digit.to_string(),
+            Glyph::Comma => "comma".to_string(),
+            Glyph::Dot => "dot".to_string(),
+            Glyph::Space => "space".to_string(),
+            Glyph::Thousand => "k".to_string(),
+            Glyph::Million => "m".to_string(),
+            Glyph::Billion => "b".to_string(),
+            Glyph::Trillion => "t".to_string(),
+            Glyph::Prefix => "prefix".to_string(),
+            Glyph::Suffix => "suffix".to_string(),
+        }
+    }
+
+    /// Parse the file stem of a theme image, e.g. `7` or `comma`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        let glyph = match name {
+            "comma" => Glyph::Comma,
+            "dot" => Glyph::Dot,
+            "space" => Glyph::Space,
+            "k" => Glyph::Thousand,
+            "m" => Glyph::Million,
+            "b" => Glyph::Billion,
+            "t" => Glyph::Trillion,
+            "prefix" => Glyph::Prefix,
+            "suffix" => Glyph::Suffix,
+            _ => match name.parse::<u32>() {
+                Ok(digit) if digit < 10 => Glyph::Digit(digit),
+                _ => return None,
+            },
+        };
+        Some(glyph)
+    }
+
+    pub fn is_digit(&self) -> bool {
+        matches!(self, Glyph::Digit(_))
+    }
+}
+
+/// Per request rendering options, theme manifests may override some.
+#[derive(Debug, Clone)]
+pub struct RenderOptions {
+    // minimal amount of digits, padded with zeros
+    pub digits_count: u32,
+    pub pixelated: bool,
+    pub notation: Notation,
+    pub marks: NumberMarks,
+    // scale on top of the theme's own scale
+    pub scale: Option<f32>,
+    // exact output height, wins over `scale`
+    pub height: Option<u32>,
+    // upper bound of the final scale
+    pub max_scale: f32,
+    // overrides of the box settings of the theme manifest
+    pub gap: Option<i32>,
+    pub padding: Option<u32>,
+    pub background: Option<Rgba<u8>>,
+    // multiplies the alpha of the background
+    pub background_opacity: Option<f32>,
+    pub border: Option<Rgba<u8>>,
+    pub border_width: Option<u32>,
+    pub radius: Option<u32>,
+    pub align: Option<Align>,
+    pub monospace: Option<bool>,
+    // svg links glyph images instead of embedding them
+    pub linked: bool,
+    pub color: ColorTransform,
+}
+
+// file name and content of a file in a theme folder
+pub type ThemeFile = (String, std::io::Result<Vec<u8>>);
+
+/// Where a theme was loaded from.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ThemeSource {
+    // built into the binary
+    Embedded,
+    // themes_dir or uploaded
+    External,
+}
+
 #[derive(Debug, Clone)]
 pub struct Theme {
-    digits: HashMap<u32, DynamicImageWithFormat>,
-    svg_digits: HashMap<u32, SvgImage>,
+    name: String,
+    source: ThemeSource,
+    // changes with the glyph images, busts caches of linked glyphs
+    version: u64,
+    glyphs: HashMap<Glyph, DynamicImageWithFormat>,
+    svg_glyphs: HashMap<Glyph, SvgImage>,
+    // built-in stand-ins for separators and units the theme lacks
+    fallback_glyphs: HashMap<Glyph, DynamicImageWithFormat>,
+    svg_fallback_glyphs: HashMap<Glyph, SvgImage>,
+    // mime type and bytes of every glyph, fallbacks included, encoded once
+    // for linked svg
+    glyph_assets: HashMap<Glyph, (&'static str, Bytes)>,
+    manifest: ThemeManifest,
 }
 
+const FALLBACK_GLYPHS: [Glyph; 7] = [
+    Glyph::Comma,
+    Glyph::Dot,
+    Glyph::Space,
+    Glyph::Thousand,
+    Glyph::Million,
+    Glyph::Billion,
+    Glyph::Trillion,
+];
+
 impl Theme {
-    fn new(digits: HashMap<u32, DynamicImageWithFormat>) -> Self {
-        let mut svg_digits = HashMap::new();
-        for (key, val) in digits.iter() {
-            svg_digits.insert(*key, val.into());
+    fn new(
+        name: &str,
+        glyphs: HashMap<Glyph, DynamicImageWithFormat>,
+        manifest: ThemeManifest,
+    ) -> Self {
+        let mut glyph_assets = HashMap::new();
+        let mut svg_glyphs = HashMap::new();
+        for (glyph, image) in glyphs.iter() {
+            let (mime_type, data) = image.source();
+            svg_glyphs.insert(*glyph, SvgImage::embed(image, mime_type, &data));
+            glyph_assets.insert(*glyph, (mime_type, Bytes::from(data)));
+        }
+
+        let mut glyph_data: Vec<(String, &str)> = svg_glyphs
+            .iter()
+            .map(|(glyph, image): (&Glyph, &SvgImage)| (glyph.name(), image.data()))
+            .collect();
+        glyph_data.sort();
+        // stable across builds, linked glyphs are cached for a year
+        let version = cache::fnv1a(
+            glyph_data
+                .iter()
+                .flat_map(|(name, data)| [name.as_bytes(), b"\0", data.as_bytes(), b"\0"]),
+        );
+
+        let digit_height = glyphs
+            .iter()
+            .filter(|(glyph, _)| glyph.is_digit())
+            .map(|(_, image)| image.height())
+            .max()
+            .unwrap_or(0);
+        let mut fallback_glyphs = HashMap::new();
+        let mut svg_fallback_glyphs = HashMap::new();
+        for glyph in FALLBACK_GLYPHS {
+            if glyphs.contains_key(&glyph) {
+                continue;
+            }
+            if let Some(image) = fallback::render(glyph, digit_height) {
+                let image =
+                    DynamicImageWithFormat::new(DynamicImage::ImageRgba8(image), ImageFormat::Png);
+                let (mime_type, data) = image.source();
+                svg_fallback_glyphs.insert(glyph, SvgImage::embed(&image, mime_type, &data));
+                glyph_assets.insert(glyph, (mime_type, Bytes::from(data)));
+                fallback_glyphs.insert(glyph, image);
+            }
+        }
+
+        Theme {
+            name: name.to_string(),
+            source: ThemeSource::External,
+            version,
+            glyphs,
+            svg_glyphs,
+            fallback_glyphs,
+            svg_fallback_glyphs,
+            glyph_assets,
+            manifest,
         }
-        Theme { digits, svg_digits }
     }
 
-    pub fn gen_webp(&self, number: u64, digits_count: u32) -> ImageResult<DynamicImageWithFormat> {
-        let number_digits = utils::u64_to_digit(number, digits_count);
+    /// Build a theme from the files of its folder, files that fail are
+    /// left out and reported. `None` when a digit is missing.
+    pub fn load(theme_name: &str, files: Vec<ThemeFile>) -> (Option<Self>, ThemeReport) {
+        let mut report = ThemeReport::new(theme_name);
+        let mut glyphs = HashMap::new();
+        let mut manifest = ThemeManifest::default();
+
+        // the manifest tells which file is a sprite sheet
+        let (manifest_files, files): (Vec<ThemeFile>, Vec<ThemeFile>) = files
+            .into_iter()
+            .partition(|(file_name, _)| file_name == MANIFEST_FILE_NAME);
+        for (_, bytes) in manifest_files {
+            let parsed = bytes
+                .map_err(|e| e.into())
+                .and_then(|bytes| ThemeManifest::from_bytes(&bytes));
+            match parsed {
+                Ok(parsed) => manifest = parsed,
+                Err(e) => report.bad_manifest = Some(e.to_string()),
+            }
+        }
+        let mut sprite_bytes = None;
+        let mut font_bytes = None;
+
+        for (file_name, bytes) in files {
+            if manifest
+                .sprite
+                .as_ref()
+                .is_some_and(|sprite| sprite.file == file_name)
+            {
+                sprite_bytes = Some(bytes);
+                continue;
+            }
+            if manifest
+                .font
+                .as_ref()
+                .is_some_and(|font| font.file == file_name)
+            {
+                font_bytes = Some(bytes);
+                continue;
+            }
+
+            let glyph = Path::new(&file_name)
+                .file_stem()
+                .and_then(|stem| Glyph::from_name(&stem.to_string_lossy()));
+            let glyph = match glyph {
+                Some(glyph) => glyph,
+                None => {
+                    report.unknown_files.push(file_name);
+                    continue;
+                }
+            };
+            if glyphs.contains_key(&glyph) {
+                report
+                    .bad_files
+                    .push((file_name, "another file has the same glyph".to_string()));
+                continue;
+            }
 
-        let mut multparts = Vec::new();
-        let mut height = 0;
-        let mut width = 0;
+            let image = bytes
+                .map_err(|e| e.into())
+                .and_then(|bytes| DynamicImageWithFormat::from_bytes(&bytes));
+            match image {
+                Ok(image) => {
+                    report.add_glyph(
+                        &file_name,
+                        glyph,
+                        image.format_name(),
+                        image.height(),
+                        image.frames.len(),
+                    );
+                    glyphs.insert(glyph, image);
+                }
+                Err(e) => report.bad_files.push((file_name, e.to_string())),
+            }
+        }
 
-        for digit in number_digits {
-            // digit must be exist
-            let digit = self.digits.get(&digit).unwrap();
-            let digit_width = digit.width();
-            let digit_height = digit.height();
+        if let Some(sprite) = &manifest.sprite {
+            match sprite_bytes {
+                // glyphs given as their own files win over the sheet
+                Some(bytes) => Self::slice_sprite(sprite, bytes, &mut glyphs, &mut report),
+                None => report
+                    .bad_files
+                    .push((sprite.file.clone(), "sprite sheet not found".to_string())),
+            }
+        }
+        if let Some(font) = &manifest.font {
+            match font_bytes {
+                Some(bytes) => Self::write_font(font, bytes, &mut glyphs, &mut report),
+                None => report
+                    .bad_files
+                    .push((font.file.clone(), "font not found".to_string())),
+            }
+        }
 
-            multparts.push((width, digit));
-            height = height.max(digit_height);
-            width += digit_width;
+        report.missing_digits = (0..10)
+            .filter(|digit| !glyphs.contains_key(&Glyph::Digit(*digit)))
+            .collect();
+        if report.is_broken() {
+            return (None, report);
         }
+        (Some(Theme::new(theme_name, glyphs, manifest)), report)
+    }
 
-        let mut concated_img = RgbaImage::new(width, height);
+    /// Cut the cells of a sprite sheet into glyphs the theme has no file for.
+    fn slice_sprite(
+        sprite: &SpriteSheet,
+        bytes: std::io::Result<Vec<u8>>,
+        glyphs: &mut HashMap<Glyph, DynamicImageWithFormat>,
+        report: &mut ThemeReport,
+    ) {
+        let image = bytes
+            .map_err(|e| e.into())
+            .and_then(|bytes| DynamicImageWithFormat::from_bytes(&bytes));
+        let image = match image {
+            Ok(image) => image,
+            Err(e) => {
+                report.bad_files.push((sprite.file.clone(), e.to_string()));
+                return;
+            }
+        };
 
-        for (x, digit) in multparts {
-            concated_img.copy_from(digit.as_raw(), x, 0)?;
+        let (cell_width, cell_height) = sprite.cell_size(image.width(), image.height());
+        let columns = image.width() / cell_width.max(1);
+        if cell_width == 0 || columns == 0 {
+            report.bad_files.push((
+                sprite.file.clone(),
+                "sprite sheet smaller than a cell".to_string(),
+            ));
+            return;
         }
 
-        Ok(DynamicImageWithFormat {
-            format: image::ImageFormat::WebP,
-            data: DynamicImage::ImageRgba8(concated_img),
+        for (idx, name) in sprite.glyphs.iter().enumerate() {
+            let glyph = match Glyph::from_name(name) {
+                Some(glyph) if !glyphs.contains_key(&glyph) => glyph,
+                _ => continue,
+            };
+            let (x, y) = (
+                idx as u32 % columns * cell_width,
+                idx as u32 / columns * cell_height,
+            );
+            let label = format!("{}[{}]", sprite.file, name);
+            if y + cell_height > image.height() {
+                report
+                    .bad_files
+                    .push((label, "cell outside of the sprite sheet".to_string()));
+                continue;
+            }
+
+            let cell = image.crop(x, y, cell_width, cell_height);
+            report.add_glyph(
+                &label,
+                glyph,
+                cell.format_name(),
+                cell.height(),
+                cell.frames.len(),
+            );
+            glyphs.insert(glyph, cell);
+        }
+    }
+
+    /// Write the glyphs the theme has no file for with its font.
+    fn write_font(
+        font: &FontGlyphs,
+        bytes: std::io::Result<Vec<u8>>,
+        glyphs: &mut HashMap<Glyph, DynamicImageWithFormat>,
+        report: &mut ThemeReport,
+    ) {
+        let written = bytes
+            .map_err(|e| e.into())
+            .and_then(|bytes| font::render(font, bytes));
+        let written = match written {
+            Ok(written) => written,
+            Err(e) => {
+                report.bad_files.push((font.file.clone(), e.to_string()));
+                return;
+            }
+        };
+
+        for (glyph, vector) in written {
+            if glyphs.contains_key(&glyph) {
+                continue;
+            }
+            let label = format!("{}[{}]", font.file, glyph.name());
+            match DynamicImageWithFormat::from_vector(vector) {
+                Ok(image) => {
+                    report.add_glyph(&label, glyph, "font", image.height(), 0);
+                    glyphs.insert(glyph, image);
+                }
+                Err(e) => report.bad_files.push((label, e.to_string())),
+            }
+        }
+    }
+
+    /// Build a theme from uploaded files. Unlike themes found in
+    /// `themes_dir`, every file has to be the manifest or a decodable glyph.
+    pub fn from_files(
+        theme_name: &str,
+        files: &[(String, Vec<u8>)],
+    ) -> std::io::Result<(Self, ThemeReport)> {
+        let files = files
+            .iter()
+            .map(|(file_name, bytes)| (file_name.clone(), Ok(bytes.clone())))
+            .collect();
+        let (theme, report) = Self::load(theme_name, files);
+
+        match theme {
+            Some(theme)
+                if report.bad_files.is_empty()
+                    && report.unknown_files.is_empty()
+                    && report.bad_manifest.is_none() =>
+            {
+                Ok((theme, report))
+            }
+            _ => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                report.problems().join("; "),
+            )),
+        }
+    }
+
+    /// A glyph encoded in the format of its file, for linked svg. The same
+    /// bytes svg embeds otherwise.
+    pub fn glyph_asset(&self, glyph_name: &str) -> Option<(&'static str, Bytes)> {
+        self.glyph_assets
+            .get(&Glyph::from_name(glyph_name)?)
+            .cloned()
+    }
+
+    pub fn manifest(&self) -> &ThemeManifest {
+        &self.manifest
+    }
+
+    pub fn source(&self) -> ThemeSource {
+        self.source
+    }
+
+    /// Width and height of the largest digits.
+    pub fn digit_size(&self) -> (u32, u32) {
+        let digits = self.glyphs.iter().filter(|(glyph, _)| glyph.is_digit());
+        digits.fold((0, 0), |(width, height), (_, image)| {
+            (width.max(image.width()), height.max(image.height()))
         })
     }
 
-    pub fn gen_svg(
+    pub fn is_animated(&self) -> bool {
+        self.glyphs.values().any(|glyph| !glyph.frames.is_empty())
+    }
+
+    fn is_pixelated(&self, pixelated: bool) -> bool {
+        self.manifest.pixelated.unwrap_or(pixelated)
+    }
+
+    pub fn has_glyph(&self, glyph: Glyph) -> bool {
+        self.glyphs.contains_key(&glyph)
+    }
+
+    fn glyph(&self, glyph: &Glyph) -> Option<&DynamicImageWithFormat> {
+        self.glyphs
+            .get(glyph)
+            .or_else(|| self.fallback_glyphs.get(glyph))
+    }
+
+    fn svg_glyph(&self, glyph: &Glyph) -> Option<&SvgImage> {
+        self.svg_glyphs
+            .get(glyph)
+            .or_else(|| self.svg_fallback_glyphs.get(glyph))
+    }
+
+    /// The glyphs to draw for a number, wrapped in the end caps of the theme.
+    fn glyphs_of(&self, number: u64, options: &RenderOptions) -> Vec<Glyph> {
+        let mut glyphs = Vec::new();
+        if self.has_glyph(Glyph::Prefix) {
+            glyphs.push(Glyph::Prefix);
+        }
+        glyphs.extend(notation::to_glyphs(
+            number,
+            options.digits_count,
+            options.notation,
+            options.marks,
+        ));
+        if self.has_glyph(Glyph::Suffix) {
+            glyphs.push(Glyph::Suffix);
+        }
+        glyphs
+    }
+
+    /// Box settings of the request on top of the ones of the manifest.
+    fn backdrop(&self, options: &RenderOptions) -> Backdrop {
+        let manifest_color = |color: &Option<String>| color.as_deref().and_then(utils::parse_color);
+
+        let background = options
+            .background
+            .or_else(|| manifest_color(&self.manifest.background))
+            .map(|mut color| {
+                if let Some(opacity) = options.background_opacity {
+                    color.0[3] = (color.0[3] as f32 * opacity.clamp(0.0, 1.0)).round() as u8;
+                }
+                color
+            });
+        let border = options
+            .border
+            .or_else(|| manifest_color(&self.manifest.border));
+        // a border colour alone asks for a 1px border, a width alone for a
+        // black one
+        let border_width = options
+            .border_width
+            .or(self.manifest.border_width)
+            .unwrap_or(if border.is_some() { 1 } else { 0 });
+
+        Backdrop {
+            padding: options.padding.unwrap_or(self.manifest.padding),
+            background,
+            border: border.or(Some(Rgba([0, 0, 0, 255]))),
+            border_width,
+            radius: options.radius.unwrap_or(self.manifest.radius),
+        }
+    }
+
+    /// Offset of every glyph the theme has and the size of the counter
+    /// before scaling, glyphs the theme lacks are left out.
+    fn layout(
+        &self,
+        glyphs: &[Glyph],
+        options: &RenderOptions,
+        padding: u32,
+    ) -> (Vec<(Glyph, u32, u32)>, u32, u32) {
+        let gap = options.gap.unwrap_or(self.manifest.gap);
+        let align = options.align.unwrap_or(self.manifest.align);
+        let monospace = options.monospace.unwrap_or(self.manifest.monospace);
+
+        let images: Vec<(Glyph, &DynamicImageWithFormat)> = glyphs
+            .iter()
+            .filter_map(|glyph| self.glyph(glyph).map(|image| (*glyph, image)))
+            .collect();
+        let height = images
+            .iter()
+            .map(|(_, image)| image.height())
+            .max()
+            .unwrap_or(0);
+        // in monospace every digit is centred in a cell as wide as the widest
+        let cell_width = images
+            .iter()
+            .filter(|(glyph, _)| monospace && glyph.is_digit())
+            .map(|(_, image)| image.width())
+            .max();
+
+        let mut offsets = Vec::new();
+        let mut width = 0;
+        // right edge of the previous glyph
+        let mut cursor: i64 = 0;
+
+        for (glyph, image) in images {
+            let slot_width = match cell_width {
+                Some(cell_width) if glyph.is_digit() => cell_width,
+                _ => image.width(),
+            };
+            let x = if offsets.is_empty() {
+                0
+            } else {
+                (cursor + gap as i64).max(0)
+            };
+            let y = match align {
+                Align::Top => 0,
+                Align::Middle => (height - image.height()) / 2,
+                Align::Bottom => height - image.height(),
+            };
+
+            offsets.push((
+                glyph,
+                x as u32 + (slot_width - image.width()) / 2 + padding,
+                y + padding,
+            ));
+            cursor = x + slot_width as i64;
+            width = width.max(cursor as u32);
+        }
+
+        (offsets, width + 2 * padding, height + 2 * padding)
+    }
+
+    fn layout_of(
         &self,
         number: u64,
-        digits_count: u32,
-        pixelated: bool,
-    ) -> ImageResult<SvgImage> {
-        // convert u32 to digits vector with extra digit
-        let number_digits = utils::u64_to_digit(number, digits_count);
+        options: &RenderOptions,
+    ) -> (Vec<(Glyph, u32, u32)>, u32, u32, Backdrop) {
+        let glyphs = self.glyphs_of(number, options);
+        let backdrop = self.backdrop(options);
+        let (offsets, width, height) = self.layout(&glyphs, options, backdrop.padding);
+        (offsets, width, height, backdrop)
+    }
+
+    /// Final scale of a counter `height` pixels high before scaling.
+    fn scale_of(&self, height: u32, options: &RenderOptions) -> f32 {
+        let scale = match options.height {
+            Some(target_height) if height > 0 => target_height as f32 / height as f32,
+            _ => self.manifest.scale * options.scale.unwrap_or(1.0),
+        };
+
+        if !scale.is_finite() || scale <= 0.0 {
+            return self.manifest.scale.min(options.max_scale);
+        }
+        scale.min(options.max_scale)
+    }
 
+    fn scaled_size(width: u32, height: u32, scale: f32) -> (u32, u32) {
+        (
+            ((width as f32 * scale).round() as u32).max(1),
+            ((height as f32 * scale).round() as u32).max(1),
+        )
+    }
+
+    fn scale_image(image: RgbaImage, scale: f32, pixelated: bool) -> RgbaImage {
+        if scale == 1.0 {
+            return image;
+        }
+
+        let (width, height) = Self::scaled_size(image.width(), image.height(), scale);
+        let filter = if pixelated {
+            FilterType::Nearest
+        } else {
+            FilterType::Lanczos3
+        };
+        imageops::resize(&image, width, height, filter)
+    }
+
+    /// Vector glyphs of a layout rasterized at the final scale, drawn after
+    /// the rest is scaled so they stay sharp.
+    fn vector_layer(
+        &self,
+        offsets: &[(Glyph, u32, u32)],
+        scale: f32,
+        color: &ColorTransform,
+    ) -> Vec<(RgbaImage, i64, i64)> {
+        offsets
+            .iter()
+            .filter_map(|(glyph, x, y)| {
+                let vector = self.glyph(glyph)?.vector()?;
+                let mut image = vector.rasterize(scale)?;
+                color.apply(&mut image);
+                Some((
+                    image,
+                    (*x as f32 * scale).round() as i64,
+                    (*y as f32 * scale).round() as i64,
+                ))
+            })
+            .collect()
+    }
+
+    pub fn gen_raster(
+        &self,
+        number: u64,
+        format: ImageFormat,
+        options: &RenderOptions,
+    ) -> ImageResult<DynamicImageWithFormat> {
+        let (offsets, width, height, backdrop) = self.layout_of(number, options);
+
+        let mut concated_img = backdrop
+            .render(width, height)
+            .unwrap_or_else(|| RgbaImage::new(width, height));
+
+        for (glyph, x, y) in &offsets {
+            let image = self.glyph(glyph).unwrap();
+            if image.vector().is_some() {
+                continue;
+            }
+            if options.color.is_identity() {
+                imageops::overlay(&mut concated_img, image.as_raw(), *x as i64, *y as i64);
+            } else {
+                imageops::overlay(
+                    &mut concated_img,
+                    &image.colored_frame(0, &options.color),
+                    *x as i64,
+                    *y as i64,
+                );
+            }
+        }
+
+        let scale = self.scale_of(height, options);
+        let mut concated_img =
+            Self::scale_image(concated_img, scale, self.is_pixelated(options.pixelated));
+        for (image, x, y) in self.vector_layer(&offsets, scale, &options.color) {
+            imageops::overlay(&mut concated_img, &image, x, y);
+        }
+        Ok(DynamicImageWithFormat::new(
+            DynamicImage::ImageRgba8(concated_img),
+            format,
+        ))
+    }
+
+    pub fn gen_png(
+        &self,
+        number: u64,
+        options: &RenderOptions,
+    ) -> Result<DynamicImageWithFormat, Box<dyn Error>> {
+        // resvg has no way to fetch linked glyphs
+        let options = RenderOptions {
+            linked: false,
+            ..options.clone()
+        };
+        let svg_img = self.gen_svg(number, &options, None)?;
+        let image = svg_img.rasterize(self.is_pixelated(options.pixelated))?;
+
+        Ok(DynamicImageWithFormat::new(
+            DynamicImage::ImageRgba8(image),
+            ImageFormat::Png,
+        ))
+    }
+
+    pub fn gen_animated(
+        &self,
+        number: u64,
+        format: ImageFormat,
+        options: &RenderOptions,
+    ) -> ImageResult<AnimatedImage> {
+        let (offsets, width, height, backdrop) = self.layout_of(number, options);
+
+        let multparts: Vec<(u32, u32, &DynamicImageWithFormat)> = offsets
+            .iter()
+            .map(|(glyph, x, y)| (*x, *y, self.glyph(glyph).unwrap()))
+            .collect();
+
+        let tracks: Vec<Vec<u32>> = multparts
+            .iter()
+            .map(|(_, _, image)| image.frame_delays())
+            .collect();
+        let canvas = backdrop
+            .render(width, height)
+            .unwrap_or_else(|| RgbaImage::new(width, height));
+
+        let pixelated = self.is_pixelated(options.pixelated);
+        let scale = self.scale_of(height, options);
+        let vector_layer = self.vector_layer(&offsets, scale, &options.color);
+        let mut animated_img = AnimatedImage::new(format);
+        for step in animation::merge_timeline(&tracks) {
+            let mut concated_img = canvas.clone();
+            for ((x, y, image), frame_idx) in multparts.iter().zip(step.frame_indices) {
+                if image.vector().is_some() {
+                    continue;
+                }
+                imageops::overlay(
+                    &mut concated_img,
+                    &image.colored_frame(frame_idx, &options.color),
+                    *x as i64,
+                    *y as i64,
+                );
+            }
+            let mut concated_img = Self::scale_image(concated_img, scale, pixelated);
+            for (image, x, y) in &vector_layer {
+                imageops::overlay(&mut concated_img, image, *x, *y);
+            }
+            animated_img.push_frame(concated_img, step.delay_ms);
+        }
+
+        Ok(animated_img)
+    }
+
+    /// Where `/themes/:name/:glyph` serves a glyph of this version of the theme.
+    fn glyph_url(&self, glyph: Glyph) -> String {
+        format!(
+            "/themes/{}/{}?v={:x}",
+            utils::percent_encode(&self.name),
+            glyph.name(),
+            self.version
+        )
+    }
+
+    /// Backdrop and glyphs of a counter in svg, with the size of the
+    /// counter before scaling. Every distinct glyph is embedded once in
+    /// `<defs>`, ids start with `id_prefix`.
+    fn svg_body(
+        &self,
+        number: u64,
+        options: &RenderOptions,
+        id_prefix: &str,
+    ) -> (String, u32, u32) {
+        let (offsets, width, height, backdrop) = self.layout_of(number, options);
+
+        let mut defs = String::new();
+        let mut defined = Vec::new();
         let mut multparts = String::new();
-        let mut height = 0;
-        let mut width = 0;
 
-        for digit in number_digits {
-            // digit must be exist
-            let digit = self.svg_digits.get(&digit).unwrap();
+        for (glyph, x, y) in offsets {
+            let id = format!("{id_prefix}{}", glyph.name());
+            if !defined.contains(&glyph) {
+                defined.push(glyph);
+
+                let vector = self.glyph(&glyph).and_then(|image| image.vector());
+                if let (Some(vector), false) = (vector, options.linked) {
+                    // inlined as a nested svg, with its ids kept apart
+                    let inlined = vector.to_svg(&format!("{id}-"));
+                    if let Some(rest) = inlined.strip_prefix("<svg") {
+                        defs.push_str(&format!("<svg id=\"{id}\"{rest}\n"));
+                    }
+                    multparts.push_str(&format!("<use href=\"#{id}\" x=\"{x}\" y=\"{y}\" />\n"));
+                    continue;
+                }
 
-            let digit_width = digit.width;
-            let digit_height = digit.height;
-            let data = &digit.data;
+                let svg_glyph = self.svg_glyph(&glyph).unwrap();
+                let glyph_width = svg_glyph.width;
+                let glyph_height = svg_glyph.height;
+                let data = match options.linked {
+                    true => Cow::Owned(self.glyph_url(glyph)),
+                    false => Cow::Borrowed(svg_glyph.data()),
+                };
 
-            multparts.push_str(&format!("<image x=\"{width}\" y=\"0\" width=\"{digit_width}\" height=\"{digit_height}\" href=\"{data}\" />\n"));
+                defs.push_str(&format!("<image id=\"{id}\" width=\"{glyph_width}\" height=\"{glyph_height}\" href=\"{data}\" />\n"));
+            }
 
-            width += digit.width;
-            height = height.max(digit.height);
+            multparts.push_str(&format!("<use href=\"#{id}\" x=\"{x}\" y=\"{y}\" />\n"));
         }
 
+        let filter_id = format!("{id_prefix}color");
+        let filter = options.color.svg_filter(&filter_id);
+        let group = match filter.is_empty() {
+            true => "<g>".to_string(),
+            false => format!("<g filter=\"url(#{filter_id})\">"),
+        };
+        let body = format!(
+            "<defs>\n{defs}{filter}</defs>\n{}{group}\n{multparts}</g>\n",
+            backdrop.svg_rect(width, height)
+        );
+        (body, width, height)
+    }
+
+    /// Render the counter as svg. With a `dark` theme, its glyphs are
+    /// embedded too and shown instead when the viewer prefers a dark
+    /// colour scheme.
+    pub fn gen_svg(
+        &self,
+        number: u64,
+        options: &RenderOptions,
+        dark: Option<&Theme>,
+    ) -> ImageResult<SvgImage> {
+        let (body, width, height) = self.svg_body(number, options, "glyph-");
+
+        let (scaled_width, scaled_height) =
+            Self::scaled_size(width, height, self.scale_of(height, options));
+
         let mut svg_payload = String::new();
         svg_payload.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
-        svg_payload.push_str(&format!("<svg width=\"{width}\" height=\"{height}\" version=\"1.1\" xmlns=\"http://www.w3.org/2000/svg\" xmlns:xlink=\"http://www.w3.org/1999/xlink\""));
+        svg_payload.push_str(&format!("<svg width=\"{scaled_width}\" height=\"{scaled_height}\" viewBox=\"0 0 {width} {height}\" version=\"1.1\" xmlns=\"http://www.w3.org/2000/svg\" xmlns:xlink=\"http://www.w3.org/1999/xlink\""));
 
-        if pixelated {
+        if self.is_pixelated(options.pixelated) {
             svg_payload.push_str(" style='image-rendering: pixelated;'");
         }
 
         svg_payload.push_str(">\n");
         svg_payload.push_str(&format!("<title>{}</title>\n", number));
-        svg_payload.push_str(&format!("<g>{multparts}</g>\n"));
+        match dark {
+            Some(dark) => {
+                let (dark_body, dark_width, dark_height) =
+                    dark.svg_body(number, options, "dark-glyph-");
+                svg_payload.push_str("<style>.dark { display: none; } @media (prefers-color-scheme: dark) { .light { display: none; } .dark { display: inline; } }</style>\n");
+                svg_payload.push_str(&format!("<g class=\"light\">\n{body}</g>\n"));
+                // fit the dark counter into the box of the light one
+                svg_payload.push_str(&format!("<svg class=\"dark\" width=\"{width}\" height=\"{height}\" viewBox=\"0 0 {dark_width} {dark_height}\">\n{dark_body}</svg>\n"));
+            }
+            None => svg_payload.push_str(&body),
+        }
         svg_payload.push_str("</svg>");
 
         Ok(SvgImage {
-            width,
-            height,
+            width: scaled_width,
+            height: scaled_height,
             data: svg_payload,
         })
     }
@@ -213,92 +1151,162 @@ struct ThemeAssets;
 #[derive(Debug, Clone)]
 pub struct ThemeManager {
     themes_dir: String,
-    themes: HashMap<String, Theme>,
+    themes: HashMap<String, Arc<Theme>>,
+    // what loading found for every theme, broken ones included
+    reports: HashMap<String, ThemeReport>,
 }
 
 impl ThemeManager {
     pub fn new(themes_dir: &str) -> std::io::Result<Self> {
-        let mut themes = HashMap::new();
-
-        let themes_from_internal = Self::load_themes_from_internal();
-        let themes_from_external = Self::load_themes_from_external(themes_dir);
-
-        match themes_from_internal {
-            Ok(assets) => themes.extend(assets),
-            Err(e) => println!("[Warn] Failed to load internal assets {:?}", e),
+        let mut theme_manager = ThemeManager {
+            themes_dir: themes_dir.to_string(),
+            themes: HashMap::new(),
+            reports: HashMap::new(),
         };
-        match themes_from_external {
-            Ok(assets) => themes.extend(assets),
+
+        theme_manager.add_loaded(Self::load_themes_from_internal());
+        match Self::load_themes_from_external(themes_dir) {
+            Ok(loaded) => theme_manager.add_loaded(loaded),
             Err(e) => println!("[Warn] Failed to load external assets {:?}", e),
         };
 
-        let theme_manager = ThemeManager {
-            themes_dir: themes_dir.to_string(),
-            themes,
-        };
         Ok(theme_manager)
     }
 
-    fn load_themes_from_internal() -> std::io::Result<HashMap<String, Theme>> {
-        let mut assets: HashMap<String, HashMap<u32, DynamicImageWithFormat>> = HashMap::new();
-
-        // iter embed assets
-        for file_path in ThemeAssets::iter() {
-            // assumption: the path is <theme_name>/<digit>.ext
-
-            let path = std::path::Path::new(file_path.as_ref());
-            let mut path_iter = path.components().rev();
-            let file_name = path.file_stem();
+    /// Load all themes again. A theme whose folder is still there but
+    /// no longer loads keeps its current version, an override of an
+    /// embedded theme included. A missing or unreadable `themes_dir` fails
+    /// the whole reload.
+    pub fn reload(&self) -> std::io::Result<Self> {
+        let mut theme_manager = ThemeManager {
+            themes_dir: self.themes_dir.clone(),
+            themes: HashMap::new(),
+            reports: HashMap::new(),
+        };
+        theme_manager.add_loaded(Self::load_themes_from_internal());
+        theme_manager.add_loaded(Self::load_themes_from_external(&self.themes_dir)?);
 
-            let _ = path_iter.next();
-            let theme_name = path_iter.next();
-            if theme_name.is_none() || file_name.is_none() {
+        for (name, theme) in &self.themes {
+            if theme.source != ThemeSource::External {
                 continue;
             }
-
-            let file_name = file_name.unwrap();
-
-            let digit = file_name.to_string_lossy().parse();
-            if digit.is_err() {
-                continue;
+            // a broken override leaves the embedded theme in its place
+            let overridden = match theme_manager.themes.get(name) {
+                Some(reloaded) if reloaded.source == ThemeSource::External => continue,
+                Some(_) => true,
+                None => false,
+            };
+            if Path::new(&self.themes_dir).join(name).is_dir() {
+                println!(
+                    "[Warn] failed to reload theme {}, keep the loaded one",
+                    name
+                );
+                theme_manager.themes.insert(name.clone(), theme.clone());
+                // the report of the embedded theme says it is in use
+                if let Some(report) = self.reports.get(name).filter(|_| overridden) {
+                    let mut report = report.clone();
+                    let warning = "external override failed to reload, keep the loaded one";
+                    if !report.warnings.iter().any(|known| known == warning) {
+                        report.warnings.push(warning.to_string());
+                    }
+                    theme_manager.reports.insert(name.clone(), report);
+                }
             }
+        }
 
-            let digit: u32 = digit.unwrap();
-
-            // load image
-            let theme_name = theme_name.unwrap().as_os_str().to_string_lossy();
+        Ok(theme_manager)
+    }
 
-            // init hashmap
-            let themes = assets.get(theme_name.as_ref());
-            if themes.is_none() {
-                assets.insert(theme_name.to_string(), HashMap::new());
-            }
-            let themes = assets.get_mut(theme_name.as_ref()).unwrap();
-            let image = ThemeAssets::get(file_path.as_ref()).unwrap().try_into();
+    /// Check the themes in a folder without loading the embedded ones.
+    pub fn check(themes_dir: &str) -> std::io::Result<Vec<ThemeReport>> {
+        let embedded = Self::embedded_theme_files();
+        let mut reports: Vec<ThemeReport> = Self::load_themes_from_external(themes_dir)?
+            .into_iter()
+            .map(|(theme, mut report)| {
+                if theme.is_none() && embedded.contains_key(&report.name) {
+                    report
+                        .warnings
+                        .push("external override failed, using embedded".to_string());
+                }
+                report
+            })
+            .collect();
+        reports.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(reports)
+    }
 
-            if image.is_err() {
-                continue;
+    fn add_loaded(&mut self, loaded: Vec<(Option<Theme>, ThemeReport)>) {
+        for (theme, report) in loaded {
+            match theme {
+                Some(theme) => {
+                    self.themes.insert(report.name.clone(), Arc::new(theme));
+                }
+                // a broken theme in themes_dir does not hide an embedded one
+                None if self.themes.contains_key(&report.name) => {
+                    if let Some(embedded) = self.reports.get_mut(&report.name) {
+                        embedded.add_failed_override(&report);
+                    }
+                    continue;
+                }
+                None => {}
             }
+            self.reports.insert(report.name.clone(), report);
+        }
+    }
 
-            let image = image.unwrap();
+    fn load_themes_from_internal() -> Vec<(Option<Theme>, ThemeReport)> {
+        Self::embedded_theme_files()
+            .into_iter()
+            .map(|(theme_name, files)| Self::load_embedded_theme(&theme_name, files))
+            .collect()
+    }
 
-            themes.insert(digit, image);
-        }
+    /// Load the embedded theme of a name again, e.g. once a theme in
+    /// `themes_dir` overriding it is gone.
+    pub fn load_embedded(theme_name: &str) -> Option<(Theme, ThemeReport)> {
+        let files = Self::embedded_theme_files().remove(theme_name)?;
+        let (theme, report) = Self::load_embedded_theme(theme_name, files);
+        Some((theme?, report))
+    }
 
-        // check all themes
-        let mut themes = HashMap::new();
-        for (theme_name, digits) in assets.drain() {
-            if digits.len() == 10 {
-                themes.insert(theme_name, Theme::new(digits));
-            }
+    fn embedded_theme_files() -> HashMap<String, Vec<ThemeFile>> {
+        // assumption: the path is <theme_name>/<glyph>.ext
+        let mut theme_files: HashMap<String, Vec<ThemeFile>> = HashMap::new();
+        for file_path in ThemeAssets::iter() {
+            let path = Path::new(file_path.as_ref());
+            let theme_name = path.parent().and_then(|parent| parent.file_name());
+            let file_name = path.file_name();
+            let (theme_name, file_name) = match (theme_name, file_name) {
+                (Some(theme_name), Some(file_name)) => (theme_name, file_name),
+                _ => continue,
+            };
+
+            let bytes = ThemeAssets::get(file_path.as_ref())
+                .map(|file| file.data.to_vec())
+                .ok_or(std::io::Error::from(std::io::ErrorKind::NotFound));
+            theme_files
+                .entry(theme_name.to_string_lossy().to_string())
+                .or_default()
+                .push((file_name.to_string_lossy().to_string(), bytes));
         }
-
-        Ok(themes)
+        theme_files
     }
 
-    fn load_themes_from_external(themes_dir: &str) -> std::io::Result<HashMap<String, Theme>> {
-        let mut themes = HashMap::new();
+    fn load_embedded_theme(
+        theme_name: &str,
+        files: Vec<ThemeFile>,
+    ) -> (Option<Theme>, ThemeReport) {
+        let (theme, report) = Theme::load(theme_name, files);
+        let theme = theme.map(|mut theme| {
+            theme.source = ThemeSource::Embedded;
+            theme
+        });
+        (theme, report)
+    }
 
+    fn load_themes_from_external(
+        themes_dir: &str,
+    ) -> std::io::Result<Vec<(Option<Theme>, ThemeReport)>> {
         // iter themes_dir to found all avaliable theme
         // check path
         if !Path::new(themes_dir).try_exists()? {
@@ -308,85 +1316,167 @@ impl ThemeManager {
             ));
         }
 
-        let sub_dirs = std::fs::read_dir(themes_dir)?;
-        for entry in sub_dirs {
-            if entry.is_err() {
-                continue;
-            }
-            // must be ok
-            let entry = entry.unwrap();
-
+        let mut loaded = Vec::new();
+        for entry in std::fs::read_dir(themes_dir)? {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(_) => continue,
+            };
             // skip file
-            if !entry.file_type().unwrap().is_dir() {
+            if !entry.file_type().is_ok_and(|file_type| file_type.is_dir()) {
                 continue;
             }
 
             // so now all entry is a dir represent as a theme
-            let theme_name = entry.file_name().into_string().unwrap(); // on must OS, this should be fine
-
-            // collect all image
-            let mut theme_images: HashMap<u32, DynamicImageWithFormat> = HashMap::new();
-            let mut theme_path = std::path::PathBuf::new();
-            theme_path.push(themes_dir);
-            theme_path.push(&theme_name);
-
-            let mut digit_img_count = 0;
-            for entry in std::fs::read_dir(theme_path.as_path())? {
-                if entry.is_err() {
-                    break;
-                }
-                let entry = entry.unwrap();
-                let image = DynamicImageWithFormat::open(entry.path());
-                if image.is_err() {
-                    break;
-                }
-                let image_path = entry.path();
-                let image_name = image_path.file_stem();
-                if image_name.is_none() {
-                    break;
-                }
-                let image_name = image_name.unwrap();
-                let digit = image_name.to_str().unwrap().parse::<u32>();
-                if digit.is_err() {
-                    break;
-                }
-
-                let image = image.unwrap();
-                let digit = digit.unwrap();
+            let theme_name = entry.file_name().to_string_lossy().to_string();
 
-                theme_images.insert(digit, image);
-                digit_img_count += 1;
-            }
-            // bad theme, skip
-            if digit_img_count != 10 {
+            // skip hidden dirs, uploads are staged in them
+            if theme_name.starts_with('.') {
                 continue;
             }
 
-            // add this theme to manager
-            let theme = Theme::new(theme_images);
-            themes.insert(theme_name, theme);
+            let mut files = Vec::new();
+            let mut report_dir_error = None;
+            match std::fs::read_dir(entry.path()) {
+                Ok(theme_entries) => {
+                    for theme_entry in theme_entries.flatten() {
+                        let file_name = theme_entry.file_name().to_string_lossy().to_string();
+                        if file_name.starts_with('.') || theme_entry.path().is_dir() {
+                            continue;
+                        }
+                        files.push((file_name, std::fs::read(theme_entry.path())));
+                    }
+                }
+                Err(e) => report_dir_error = Some(e),
+            }
+
+            let (theme, mut report) = Theme::load(&theme_name, files);
+            if let Some(e) = report_dir_error {
+                report.bad_files.push((theme_name.clone(), e.to_string()));
+            }
+            loaded.push((theme, report));
         }
 
-        Ok(themes)
+        Ok(loaded)
     }
+
     pub fn get(&self, theme_name: &str) -> std::io::Result<&Theme> {
         match self.themes.get(theme_name) {
-            Some(theme) => Ok(theme),
+            Some(theme) => Ok(theme.as_ref()),
             None => Err(std::io::Error::new(
                 std::io::ErrorKind::NotFound,
                 theme_name,
             )),
         }
     }
+
+    /// A theme picked at random from `pool`, or from all themes if empty,
+    /// only ones with `tag` if given.
+    pub fn random(&self, pool: &[String], tag: Option<&str>, nsfw: bool) -> Option<String> {
+        let mut candidates: Vec<&String> = self
+            .themes
+            .iter()
+            .filter(|(theme_name, _)| pool.is_empty() || pool.contains(theme_name))
+            .filter(|(_, theme)| nsfw || !theme.manifest().nsfw)
+            .filter(|(_, theme)| tag.map_or(true, |tag| theme.manifest().has_tag(tag)))
+            .map(|(theme_name, _)| theme_name)
+            .collect();
+        candidates.sort();
+        candidates
+            .choose(&mut rand::thread_rng())
+            .map(|theme_name| theme_name.to_string())
+    }
+
+    /// Loaded themes by name, with what loading them found.
+    pub fn list(&self) -> Vec<(&str, &Theme, Option<&ThemeReport>)> {
+        let mut themes: Vec<(&str, &Theme, Option<&ThemeReport>)> = self
+            .themes
+            .iter()
+            .map(|(theme_name, theme)| {
+                (
+                    theme_name.as_str(),
+                    theme.as_ref(),
+                    self.reports.get(theme_name),
+                )
+            })
+            .collect();
+        themes.sort_by_key(|(theme_name, _, _)| *theme_name);
+        themes
+    }
+
+    /// Add or replace a theme.
+    pub fn insert(&mut self, theme_name: &str, theme: Theme, report: ThemeReport) {
+        self.themes.insert(theme_name.to_string(), Arc::new(theme));
+        self.reports.insert(theme_name.to_string(), report);
+    }
+
+    pub fn remove(&mut self, theme_name: &str) -> bool {
+        self.reports.remove(theme_name);
+        self.themes.remove(theme_name).is_some()
+    }
+
+    /// Dark colour scheme companion of a theme, the one its manifest names
+    /// or else the theme called `<name>-dark`.
+    pub fn dark_variant(&self, theme_name: &str) -> Option<&Theme> {
+        let theme = self.themes.get(theme_name)?;
+        let dark_name = match &theme.manifest().dark_variant {
+            Some(dark_name) => dark_name.clone(),
+            None => format!("{theme_name}-dark"),
+        };
+        if dark_name == theme_name {
+            return None;
+        }
+        self.themes.get(&dark_name).map(|theme| theme.as_ref())
+    }
 }
 
 impl fmt::Display for ThemeManager {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         writeln!(f, "ThemeManager: {}", self.themes_dir)?;
 
+        let mut theme_names: Vec<&String> = self.themes.keys().chain(self.reports.keys()).collect();
+        theme_names.sort();
+        theme_names.dedup();
+
         let mut print_out = String::new();
-        for theme_name in self.themes.keys() {
-            print_out.push_str(&format!("  {}\n", theme_name));
+        for theme_name in theme_names {
+            let details = self
+                .reports
+                .get(theme_name)
+                .map(|report| report.details())
+                .unwrap_or_default();
+            let theme = match self.themes.get(theme_name) {
+                Some(theme) => theme,
+                None => {
+                    print_out.push_str(&format!("  {} [broken]\n", theme_name));
+                    for detail in details {
+                        print_out.push_str(&format!("    {}\n", detail));
+                    }
+                    continue;
+                }
+            };
+
+            let manifest = &theme.manifest;
+            print_out.push_str(&format!("  {}", theme_name));
+            if let Some(display_name) = &manifest.display_name {
+                print_out.push_str(&format!(" ({})", display_name));
+            }
+            if let Some(author) = &manifest.author {
+                print_out.push_str(&format!(" by {}", author));
+            }
+            if let Some(license) = &manifest.license {
+                print_out.push_str(&format!(", {}", license));
+            }
+            if let Some(credit_url) = &manifest.credit_url {
+                print_out.push_str(&format!(", {}", credit_url));
+            }
+            if manifest.nsfw {
+                print_out.push_str(" [nsfw]");
+            }
+            print_out.push('\n');
+            for detail in details {
+                print_out.push_str(&format!("    {}\n", detail));
+            }
         }
         write!(f, "{}", print_out)
     }
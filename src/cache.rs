@@ -0,0 +1,94 @@
+use std::{
+    num::NonZeroUsize,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use axum::body::Bytes;
+use lru::LruCache;
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+/// An encoded counter image.
+#[derive(Debug, Clone)]
+pub struct Rendered {
+    pub content_type: String,
+    pub data: Bytes,
+}
+
+/// Least recently used images by everything that went into rendering them.
+pub struct RenderCache {
+    // `None` when caching is turned off
+    entries: Option<Mutex<LruCache<String, Rendered>>>,
+    // bumped whenever themes change, part of every key so images of old
+    // themes are not served
+    generation: AtomicU64,
+}
+
+impl RenderCache {
+    pub fn new(capacity: usize) -> Self {
+        // a new generation every start, themes may change while down
+        let generation = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|since| since.as_nanos() as u64)
+            .unwrap_or_default();
+
+        RenderCache {
+            entries: NonZeroUsize::new(capacity)
+                .map(|capacity| Mutex::new(LruCache::new(capacity))),
+            generation: AtomicU64::new(generation),
+        }
+    }
+
+    /// Read before the themes a key is rendered from.
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Relaxed)
+    }
+
+    pub fn get(&self, key: &str) -> Option<Rendered> {
+        self.entries.as_ref()?.lock().unwrap().get(key).cloned()
+    }
+
+    pub fn insert(&self, key: String, rendered: Rendered) {
+        if let Some(entries) = &self.entries {
+            entries.lock().unwrap().put(key, rendered);
+        }
+    }
+
+    /// Drop everything, for when themes are reloaded.
+    pub fn invalidate(&self) {
+        self.generation.fetch_add(1, Ordering::Relaxed);
+        if let Some(entries) = &self.entries {
+            entries.lock().unwrap().clear();
+        }
+    }
+}
+
+/// 64 bit FNV-1a hash of the chunks one after another. Unlike
+/// `DefaultHasher` it stays the same across restarts and builds.
+pub(crate) fn fnv1a<'a>(chunks: impl IntoIterator<Item = &'a [u8]>) -> u64 {
+    chunks
+        .into_iter()
+        .flatten()
+        .fold(FNV_OFFSET_BASIS, |hash, byte| {
+            (hash ^ *byte as u64).wrapping_mul(FNV_PRIME)
+        })
+}
+
+/// Strong ETag of an encoded image, a hash of its bytes, so the same image
+/// keeps its ETag across restarts and builds.
+pub fn etag(data: &[u8]) -> String {
+    format!("\"{:016x}\"", fnv1a([data]))
+}
+
+/// Whether an `If-None-Match` header lists the ETag, weak ones included.
+pub fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    if_none_match.split(',').any(|candidate| {
+        let candidate = candidate.trim();
+        candidate == "*" || candidate.strip_prefix("W/").unwrap_or(candidate) == etag
+    })
+}
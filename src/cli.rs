@@ -1,7 +1,10 @@
-use clap::Parser;
-use clap::{arg, command};
+use std::collections::HashMap;
+
+use clap::{Parser, Subcommand};
 use serde::{Deserialize, Serialize};
 
+use crate::schedule::Schedule;
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Sqlite {
     pub path: String,
@@ -18,6 +21,7 @@ impl Default for Sqlite {
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
 pub struct Config {
     pub listen: String,
     pub port: u16,
@@ -26,6 +30,30 @@ pub struct Config {
     pub digit_count: u32,
     pub default_format: String,
     pub pixelated: bool,
+    // upper bound of `scale` and `height` in requests
+    pub max_scale: f32,
+    // reload themes when something in themes_dir changes
+    pub watch_themes: bool,
+    // bearer token of the admin api, the api is off when empty
+    pub admin_token: String,
+    // bytes an uploaded theme may take, packed and unpacked
+    pub max_theme_size: usize,
+    // rendered images kept for /demo and readonly views, 0 turns it off
+    pub render_cache_size: usize,
+    // other names of themes, e.g. `booru = "moebooru"`
+    pub theme_aliases: HashMap<String, String>,
+    // tried in order after default_theme when a theme is not loaded
+    pub fallback_themes: Vec<String>,
+    // unknown themes get an error image instead of a fallback
+    pub strict_themes: bool,
+    // whether /themes and /api/themes list nsfw themes
+    pub list_nsfw: bool,
+    // themes `theme=random` picks from, every loaded theme when empty
+    pub random_themes: Vec<String>,
+    // whether `theme=random` may pick nsfw themes
+    pub random_nsfw: bool,
+    // themes shown instead of default_theme on some days, first match wins
+    pub schedules: Vec<Schedule>,
     pub sqlite: Sqlite,
 }
 
@@ -39,6 +67,18 @@ impl Default for Config {
             digit_count: 0,
             default_format: "svg".to_string(),
             pixelated: false,
+            max_scale: 4.0,
+            watch_themes: true,
+            admin_token: String::new(),
+            max_theme_size: 8 * 1024 * 1024,
+            render_cache_size: 256,
+            theme_aliases: HashMap::new(),
+            fallback_themes: vec!["moebooru".to_string()],
+            strict_themes: false,
+            list_nsfw: false,
+            random_themes: Vec::new(),
+            random_nsfw: false,
+            schedules: Vec::new(),
             sqlite: Sqlite::default(),
         }
     }
@@ -54,8 +94,29 @@ pub struct CliArgs {
         default_value = "moe-counter-rs.toml"
     )]
     pub config_path: String,
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Manage themes
+    Themes {
+        #[command(subcommand)]
+        command: ThemesCommand,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ThemesCommand {
+    /// Report every theme in a folder and what is wrong with it
+    Check {
+        #[arg(help = "folder holding one folder per theme")]
+        themes_dir: String,
+    },
 }
 
+#[allow(clippy::expect_fun_call)]
 pub fn read_config(config_path: &str) -> Config {
     // check config file is exist
     if !std::path::Path::new(config_path)
@@ -0,0 +1,313 @@
+use image::{Rgba, RgbaImage};
+
+// weights of red, green and blue in the luminance of a colour
+const LUMINANCE: [f32; 3] = [0.2126, 0.7152, 0.0722];
+
+// rows of red, green, blue and alpha, each multiplying r, g, b, a and adding
+// an offset, like svg `feColorMatrix`
+type Matrix = [[f32; 5]; 4];
+
+const IDENTITY: Matrix = [
+    [1.0, 0.0, 0.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0, 0.0, 0.0],
+    [0.0, 0.0, 1.0, 0.0, 0.0],
+    [0.0, 0.0, 0.0, 1.0, 0.0],
+];
+
+/// Recolouring of the glyphs of a counter, the backdrop is left alone.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ColorTransform {
+    // degrees around the colour wheel
+    pub hue: Option<f32>,
+    // 0 is gray, 1 unchanged, above 1 more colourful
+    pub saturation: Option<f32>,
+    pub grayscale: bool,
+    // glyphs in shades of this colour, its alpha is the strength
+    pub tint: Option<Rgba<u8>>,
+    pub invert: bool,
+    // multiplies the alpha of the glyphs
+    pub opacity: Option<f32>,
+}
+
+// `second` applied after `first`
+fn then(first: &Matrix, second: &Matrix) -> Matrix {
+    let mut result = [[0.0; 5]; 4];
+    for (row, result_row) in result.iter_mut().enumerate() {
+        for (col, value) in result_row.iter_mut().enumerate() {
+            *value = (0..4).map(|k| second[row][k] * first[k][col]).sum();
+        }
+        result_row[4] += second[row][4];
+    }
+    result
+}
+
+// the svg `saturate` matrix
+fn saturate(amount: f32) -> Matrix {
+    let [r, g, b] = LUMINANCE;
+    let s = amount;
+    [
+        [r + (1.0 - r) * s, g - g * s, b - b * s, 0.0, 0.0],
+        [r - r * s, g + (1.0 - g) * s, b - b * s, 0.0, 0.0],
+        [r - r * s, g - g * s, b + (1.0 - b) * s, 0.0, 0.0],
+        [0.0, 0.0, 0.0, 1.0, 0.0],
+    ]
+}
+
+// the svg `hueRotate` matrix
+fn hue_rotate(degrees: f32) -> Matrix {
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    [
+        [
+            0.213 + cos * 0.787 - sin * 0.213,
+            0.715 - cos * 0.715 - sin * 0.715,
+            0.072 - cos * 0.072 + sin * 0.928,
+            0.0,
+            0.0,
+        ],
+        [
+            0.213 - cos * 0.213 + sin * 0.143,
+            0.715 + cos * 0.285 + sin * 0.140,
+            0.072 - cos * 0.072 - sin * 0.283,
+            0.0,
+            0.0,
+        ],
+        [
+            0.213 - cos * 0.213 - sin * 0.787,
+            0.715 - cos * 0.715 + sin * 0.715,
+            0.072 + cos * 0.928 + sin * 0.072,
+            0.0,
+            0.0,
+        ],
+        [0.0, 0.0, 0.0, 1.0, 0.0],
+    ]
+}
+
+// luminance times the tint colour, mixed in by the tint alpha
+fn tint(color: Rgba<u8>) -> Matrix {
+    let [r, g, b, a] = color.0.map(|channel| channel as f32 / 255.0);
+    let mut matrix = IDENTITY;
+    for (row, channel) in [r, g, b].into_iter().enumerate() {
+        for (col, weight) in LUMINANCE.into_iter().enumerate() {
+            matrix[row][col] = matrix[row][col] * (1.0 - a) + weight * channel * a;
+        }
+    }
+    matrix
+}
+
+impl ColorTransform {
+    /// The combined colour matrix, `None` when nothing changes.
+    fn matrix(&self) -> Option<Matrix> {
+        let mut steps = Vec::new();
+        if let Some(saturation) = self.saturation {
+            steps.push(saturate(saturation.max(0.0)));
+        }
+        if self.grayscale {
+            steps.push(saturate(0.0));
+        }
+        if let Some(hue) = self.hue {
+            steps.push(hue_rotate(hue));
+        }
+        if let Some(color) = self.tint {
+            steps.push(tint(color));
+        }
+        if self.invert {
+            steps.push([
+                [-1.0, 0.0, 0.0, 0.0, 1.0],
+                [0.0, -1.0, 0.0, 0.0, 1.0],
+                [0.0, 0.0, -1.0, 0.0, 1.0],
+                [0.0, 0.0, 0.0, 1.0, 0.0],
+            ]);
+        }
+        if let Some(opacity) = self.opacity {
+            let mut matrix = IDENTITY;
+            matrix[3][3] = opacity.clamp(0.0, 1.0);
+            steps.push(matrix);
+        }
+
+        steps
+            .into_iter()
+            .reduce(|matrix, step| then(&matrix, &step))
+    }
+
+    /// Recolour an image in place.
+    pub fn apply(&self, image: &mut RgbaImage) {
+        let matrix = match self.matrix() {
+            Some(matrix) => matrix,
+            None => return,
+        };
+        for pixel in image.pixels_mut() {
+            let input = pixel.0.map(|channel| channel as f32 / 255.0);
+            for (channel, row) in pixel.0.iter_mut().zip(&matrix) {
+                let value: f32 = (0..4).map(|k| row[k] * input[k]).sum::<f32>() + row[4];
+                *channel = (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+            }
+        }
+    }
+
+    /// The `<filter>` doing the same in svg, empty when nothing changes.
+    pub fn svg_filter(&self, id: &str) -> String {
+        let matrix = match self.matrix() {
+            Some(matrix) => matrix,
+            None => return String::new(),
+        };
+        let values: Vec<String> = matrix
+            .iter()
+            .flatten()
+            .map(|value| format!("{value:.4}"))
+            .collect();
+        // srgb so browsers and resvg match the raster formats
+        format!(
+            "<filter id=\"{id}\" color-interpolation-filters=\"sRGB\"><feColorMatrix type=\"matrix\" values=\"{}\" /></filter>\n",
+            values.join(" ")
+        )
+    }
+
+    pub fn is_identity(&self) -> bool {
+        self.matrix().is_none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PIXELS: [[u8; 4]; 5] = [
+        [255, 0, 0, 255],
+        [0, 128, 255, 255],
+        [10, 20, 30, 128],
+        [200, 150, 100, 0],
+        [255, 255, 255, 255],
+    ];
+
+    fn applied(color: &ColorTransform) -> Vec<[u8; 4]> {
+        let mut image = RgbaImage::new(PIXELS.len() as u32, 1);
+        for (pixel, value) in image.pixels_mut().zip(PIXELS) {
+            *pixel = Rgba(value);
+        }
+        color.apply(&mut image);
+        image.pixels().map(|pixel| pixel.0).collect()
+    }
+
+    #[test]
+    fn identity() {
+        let color = ColorTransform::default();
+        assert!(color.is_identity());
+        assert_eq!(applied(&color), PIXELS);
+        assert_eq!(color.svg_filter("color"), "");
+    }
+
+    #[test]
+    fn invert() {
+        let color = ColorTransform {
+            invert: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            applied(&color),
+            [
+                [0, 255, 255, 255],
+                [255, 127, 0, 255],
+                [245, 235, 225, 128],
+                [55, 105, 155, 0],
+                [0, 0, 0, 255],
+            ]
+        );
+    }
+
+    #[test]
+    fn full_hue_turn() {
+        let color = ColorTransform {
+            hue: Some(360.0),
+            ..Default::default()
+        };
+        assert!(!color.is_identity());
+        assert_eq!(applied(&color), PIXELS);
+    }
+
+    #[test]
+    fn grayscale() {
+        let color = ColorTransform {
+            grayscale: true,
+            ..Default::default()
+        };
+        for ([r, g, b, a], [.., alpha]) in applied(&color).into_iter().zip(PIXELS) {
+            assert!(r == g && g == b, "{r} {g} {b}");
+            assert_eq!(a, alpha);
+        }
+        let saturation = ColorTransform {
+            saturation: Some(0.0),
+            ..Default::default()
+        };
+        assert_eq!(applied(&saturation), applied(&color));
+    }
+
+    #[test]
+    fn opacity() {
+        let color = ColorTransform {
+            opacity: Some(0.5),
+            ..Default::default()
+        };
+        let alphas: Vec<u8> = applied(&color).iter().map(|pixel| pixel[3]).collect();
+        assert_eq!(alphas, [128, 128, 64, 0, 128]);
+    }
+
+    #[test]
+    fn svg_filter_matches_matrix() {
+        let color = ColorTransform {
+            hue: Some(120.0),
+            saturation: Some(1.5),
+            tint: Some(Rgba([255, 136, 0, 128])),
+            invert: true,
+            opacity: Some(0.8),
+            ..Default::default()
+        };
+        let filter = color.svg_filter("color");
+        assert!(filter.starts_with("<filter id=\"color\""));
+
+        let values = filter
+            .split("values=\"")
+            .nth(1)
+            .and_then(|rest| rest.split('"').next())
+            .unwrap();
+        let values: Vec<f32> = values
+            .split(' ')
+            .map(|value| value.parse().unwrap())
+            .collect();
+        let matrix: Vec<f32> = color.matrix().unwrap().into_iter().flatten().collect();
+        assert_eq!(values.len(), 20);
+        for (value, expected) in values.iter().zip(matrix) {
+            assert!((value - expected).abs() < 1e-4, "{value} {expected}");
+        }
+
+        // and resvg drawing the filter agrees with the raster formats
+        let rects: String = PIXELS
+            .iter()
+            .enumerate()
+            .map(|(x, [r, g, b, a])| {
+                format!(
+                    "<rect x=\"{x}\" width=\"1\" height=\"1\" fill=\"rgb({r},{g},{b})\" fill-opacity=\"{}\" />",
+                    *a as f32 / 255.0
+                )
+            })
+            .collect();
+        let svg = format!(
+            "<svg width=\"{}\" height=\"1\" xmlns=\"http://www.w3.org/2000/svg\"><defs>{filter}</defs><g filter=\"url(#color)\">{rects}</g></svg>",
+            PIXELS.len()
+        );
+        let drawn = crate::vector::VectorImage::from_bytes(svg.as_bytes())
+            .unwrap()
+            .rasterize(1.0)
+            .unwrap();
+        assert_eq!(drawn.width() as usize, PIXELS.len());
+        for (drawn, expected) in drawn.pixels().zip(applied(&color)) {
+            let close = |a: u8, b: u8| a.abs_diff(b) <= 3;
+            assert!(close(drawn[3], expected[3]), "{drawn:?} {expected:?}");
+            if expected[3] > 0 {
+                assert!(
+                    (0..3).all(|channel| close(drawn[channel], expected[channel])),
+                    "{drawn:?} {expected:?}"
+                );
+            }
+        }
+    }
+}
@@ -15,6 +15,7 @@ pub struct SqliteClient {
 }
 
 impl SqliteClient {
+    #[allow(clippy::expect_fun_call)]
     pub fn new(path: &str, table_name: &str) -> Self {
         let connection =
             rusqlite::Connection::open(path).expect(&format!("failed to open db on {}", path));
@@ -41,6 +42,7 @@ impl KVDBClient for SqliteClient {
         Ok(())
     }
 
+    #[allow(clippy::let_and_return, clippy::manual_ok_err)]
     async fn get(&self, key: &str) -> Option<Self::Value> {
         let sql = format!("SELECT value FROM {} WHERE key = ?1", self.table_name);
         let conn = self.connection.lock().await;
@@ -136,6 +138,14 @@ impl DBManager {
         self.count_on_cache(key).await
     }
 
+    /// The current count of a key, without counting.
+    pub async fn peek(&self, key: &str) -> Option<u64> {
+        if let Some(value) = self.cache.lock().await.get(key) {
+            return Some(*value);
+        }
+        self.backend.get(key).await
+    }
+
     pub async fn sync_to_backend(&self) -> Result<(), Box<dyn Error>> {
         for (key, value) in self.cache.lock().await.iter() {
             let _ = self.backend.set(key, *value).await;
@@ -0,0 +1,42 @@
+use image::RgbaImage;
+use resvg::{tiny_skia, usvg};
+
+use crate::{banner::Glyph, utils};
+
+// drawn on a canvas 20 units high, the number is the width of the glyph
+fn glyph_source(glyph: Glyph) -> Option<(f32, &'static str)> {
+    let source = match glyph {
+        Glyph::Comma => (5.0, "M3 16.5 V17.5 Q3 19 1.5 19.5"),
+        Glyph::Dot => (5.0, "M2.5 17.5 V17.6"),
+        Glyph::Space => (5.0, ""),
+        Glyph::Thousand => (10.0, "M2 4 V18 M8 9 L2 14 M4.5 12.5 L8.5 18"),
+        Glyph::Million => (13.0, "M2 18 V4 L6.5 12 L11 4 V18"),
+        Glyph::Billion => (
+            11.0,
+            "M2 11 H6 Q8.5 11 8.5 7.5 Q8.5 4 6 4 H2 V18 H6.5 Q9.5 18 9.5 14.5 Q9.5 11 6.5 11",
+        ),
+        Glyph::Trillion => (11.0, "M1.5 4 H9.5 M5.5 4 V18"),
+        _ => return None,
+    };
+    Some(source)
+}
+
+/// Render the built-in stand-in for a glyph a theme does not provide.
+/// Digits and end caps have no stand-in.
+pub fn render(glyph: Glyph, height: u32) -> Option<RgbaImage> {
+    let (glyph_width, path) = glyph_source(glyph)?;
+
+    let scale = height as f32 / 20.0;
+    let width = ((glyph_width * scale).round() as u32).max(1);
+    let height = height.max(1);
+
+    let svg = format!(
+        "<svg width=\"{width}\" height=\"{height}\" viewBox=\"0 0 {glyph_width} 20\" xmlns=\"http://www.w3.org/2000/svg\"><path d=\"{path}\" fill=\"none\" stroke=\"#888888\" stroke-width=\"2.2\" stroke-linecap=\"round\" stroke-linejoin=\"round\" /></svg>"
+    );
+    let tree = usvg::Tree::from_str(&svg, &usvg::Options::default()).ok()?;
+
+    let mut pixmap = tiny_skia::Pixmap::new(width, height)?;
+    resvg::render(&tree, tiny_skia::Transform::default(), &mut pixmap.as_mut());
+
+    Some(utils::pixmap_to_rgba(&pixmap))
+}
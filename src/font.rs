@@ -0,0 +1,137 @@
+use std::sync::Arc;
+
+use image::Rgba;
+use resvg::usvg::fontdb;
+
+use crate::{
+    banner::Glyph,
+    manifest::FontGlyphs,
+    utils,
+    vector::{VectorImage, MAX_VECTOR_SIZE},
+};
+
+// what every glyph reads as when written with a font
+fn glyph_text(glyph: Glyph, font: &FontGlyphs) -> Option<String> {
+    let text = match glyph {
+        Glyph::Digit(digit) => digit.to_string(),
+        Glyph::Comma => ",".to_string(),
+        Glyph::Dot => ".".to_string(),
+        Glyph::Space => " ".to_string(),
+        Glyph::Thousand => "k".to_string(),
+        Glyph::Million => "M".to_string(),
+        Glyph::Billion => "B".to_string(),
+        Glyph::Trillion => "T".to_string(),
+        Glyph::Prefix => font.prefix.clone()?,
+        Glyph::Suffix => font.suffix.clone()?,
+    };
+    Some(text)
+}
+
+const GLYPHS: [Glyph; 19] = [
+    Glyph::Digit(0),
+    Glyph::Digit(1),
+    Glyph::Digit(2),
+    Glyph::Digit(3),
+    Glyph::Digit(4),
+    Glyph::Digit(5),
+    Glyph::Digit(6),
+    Glyph::Digit(7),
+    Glyph::Digit(8),
+    Glyph::Digit(9),
+    Glyph::Comma,
+    Glyph::Dot,
+    Glyph::Space,
+    Glyph::Thousand,
+    Glyph::Million,
+    Glyph::Billion,
+    Glyph::Trillion,
+    Glyph::Prefix,
+    Glyph::Suffix,
+];
+
+/// Write every glyph the font has a character for as an svg document, all
+/// as high as the line of the font so they share a baseline.
+pub fn render(
+    font: &FontGlyphs,
+    bytes: Vec<u8>,
+) -> Result<Vec<(Glyph, VectorImage)>, Box<dyn std::error::Error>> {
+    let face = ttf_parser::Face::parse(&bytes, 0)?;
+    let scale = font.size / face.units_per_em() as f32;
+    let ascender = face.ascender() as f32 * scale;
+    let descender = face.descender() as f32 * scale;
+    let advance = |text: &str| -> Option<f32> {
+        text.chars()
+            .map(|c| {
+                let id = face.glyph_index(c)?;
+                Some(face.glyph_hor_advance(id)? as f32 * scale)
+            })
+            .sum()
+    };
+
+    let stroke_width = match font.stroke {
+        Some(_) => font.stroke_width,
+        None => 0.0,
+    };
+    let height = (ascender - descender + stroke_width).ceil();
+    let baseline = ascender + stroke_width / 2.0;
+    let color = utils::parse_color(&font.color).unwrap_or(Rgba([0, 0, 0, 255]));
+    let fill = utils::svg_paint("fill", color);
+    let stroke = match font.stroke.as_deref().and_then(utils::parse_color) {
+        Some(color) if stroke_width > 0.0 => {
+            utils::svg_paint("stroke", color)
+                + &format!(
+                " stroke-width=\"{stroke_width}\" paint-order=\"stroke\" stroke-linejoin=\"round\""
+            )
+        }
+        _ => String::new(),
+    };
+
+    let mut glyphs = Vec::new();
+    for glyph in GLYPHS {
+        let text = match glyph_text(glyph, font) {
+            Some(text) => text,
+            None => continue,
+        };
+        // left to the built-in stand-ins when the font lacks a character
+        let width = match advance(&text) {
+            Some(width) => (width + stroke_width).ceil().max(1.0),
+            None => continue,
+        };
+        if width > MAX_VECTOR_SIZE as f32 || height > MAX_VECTOR_SIZE as f32 {
+            return Err(Box::new(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("glyphs are larger than {MAX_VECTOR_SIZE}x{MAX_VECTOR_SIZE}"),
+            )));
+        }
+
+        let svg = format!(
+            "<svg width=\"{width}\" height=\"{height}\" xmlns=\"http://www.w3.org/2000/svg\"><text x=\"{}\" y=\"{baseline}\" font-family=\"sans-serif\" font-size=\"{}\" xml:space=\"preserve\"{fill}{stroke}>{}</text></svg>",
+            stroke_width / 2.0,
+            font.size,
+            utils::escape_xml(&text)
+        );
+        glyphs.push((glyph, svg));
+    }
+
+    // the only font these glyphs can be set in
+    let mut fontdb = fontdb::Database::new();
+    fontdb.load_font_data(bytes);
+    let family = fontdb
+        .faces()
+        .next()
+        .and_then(|face| face.families.first())
+        .map(|(family, _)| family.clone())
+        .unwrap_or_default();
+    fontdb.set_sans_serif_family(family);
+    let fontdb = Arc::new(fontdb);
+
+    glyphs
+        .into_iter()
+        .map(|(glyph, svg)| {
+            Ok((
+                glyph,
+                VectorImage::with_fonts(svg.as_bytes(), fontdb.clone())?,
+            ))
+        })
+        .collect()
+}
@@ -0,0 +1,119 @@
+use std::collections::BTreeMap;
+
+use axum::{extract::State, response::Html, Json};
+use serde::Serialize;
+
+use crate::{
+    banner::{Theme, ThemeManager, ThemeSource},
+    report::ThemeReport,
+    utils, SharedState,
+};
+
+/// A loaded theme as listed by `/api/themes`.
+#[derive(Serialize, Debug)]
+pub struct ThemeInfo {
+    pub name: String,
+    pub source: ThemeSource,
+    pub formats: Vec<String>,
+    // size of the largest digits
+    pub width: u32,
+    pub height: u32,
+    pub animated: bool,
+    // frames of every animated glyph by file name
+    pub frame_counts: BTreeMap<String, usize>,
+    pub display_name: Option<String>,
+    pub author: Option<String>,
+    pub license: Option<String>,
+    pub credit_url: Option<String>,
+    pub nsfw: bool,
+    pub tags: Vec<String>,
+}
+
+impl ThemeInfo {
+    fn new(theme_name: &str, theme: &Theme, report: Option<&ThemeReport>) -> Self {
+        let manifest = theme.manifest();
+        let (width, height) = theme.digit_size();
+        ThemeInfo {
+            name: theme_name.to_string(),
+            source: theme.source(),
+            formats: report
+                .map(|report| report.formats.iter().cloned().collect())
+                .unwrap_or_default(),
+            width,
+            height,
+            animated: theme.is_animated(),
+            frame_counts: report
+                .map(|report| report.frame_counts.clone())
+                .unwrap_or_default(),
+            display_name: manifest.display_name.clone(),
+            author: manifest.author.clone(),
+            license: manifest.license.clone(),
+            credit_url: manifest.credit_url.clone(),
+            nsfw: manifest.nsfw,
+            tags: manifest.tags.clone(),
+        }
+    }
+}
+
+/// Loaded themes by name, nsfw ones only if `nsfw`.
+fn theme_infos(theme_manager: &ThemeManager, nsfw: bool) -> Vec<ThemeInfo> {
+    theme_manager
+        .list()
+        .into_iter()
+        .filter(|(_, theme, _)| nsfw || !theme.manifest().nsfw)
+        .map(|(theme_name, theme, report)| ThemeInfo::new(theme_name, theme, report))
+        .collect()
+}
+
+/// `/api/themes`
+pub async fn api_themes(State(app_state): State<SharedState>) -> Json<Vec<ThemeInfo>> {
+    let theme_manager = app_state.theme_manager();
+    Json(theme_infos(&theme_manager, app_state.config.list_nsfw))
+}
+
+/// Whether a url from a manifest is safe to link, anything but http and
+/// https, e.g. `javascript:`, is shown as text.
+fn is_web_url(url: &str) -> bool {
+    url.split_once("://").is_some_and(|(scheme, _)| {
+        scheme.eq_ignore_ascii_case("http") || scheme.eq_ignore_ascii_case("https")
+    })
+}
+
+/// `/themes`, every theme shown through `/demo`.
+pub async fn themes_page(State(app_state): State<SharedState>) -> Html<String> {
+    let theme_manager = app_state.theme_manager();
+
+    let mut entries = String::new();
+    for info in theme_infos(&theme_manager, app_state.config.list_nsfw) {
+        let name = utils::escape_xml(&info.name);
+        let mut credits = Vec::new();
+        if let Some(author) = &info.author {
+            credits.push(format!("by {}", utils::escape_xml(author)));
+        }
+        if let Some(license) = &info.license {
+            credits.push(utils::escape_xml(license));
+        }
+        if let Some(credit_url) = &info.credit_url {
+            let text = utils::escape_xml(credit_url);
+            if is_web_url(credit_url) {
+                credits.push(format!("<a href=\"{text}\">{text}</a>"));
+            } else {
+                credits.push(text);
+            }
+        }
+        if info.nsfw {
+            credits.push("NSFW".to_string());
+        }
+
+        entries.push_str(&format!(
+            "<section>\n<h2>{}</h2>\n<p><code>theme={name}</code> {}</p>\n<img src=\"/demo?theme={}\" alt=\"{name}\" loading=\"lazy\">\n</section>\n",
+            utils::escape_xml(info.display_name.as_deref().unwrap_or(&info.name)),
+            credits.join(" · "),
+            utils::percent_encode(&info.name),
+        ));
+    }
+
+    Html(format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Themes - Moe Counter</title>\n<style>body {{ font-family: sans-serif; margin: 2em; }} section {{ margin-bottom: 2em; }} img {{ max-width: 100%; }}</style>\n</head>\n<body>\n<h1>Themes</h1>\n{entries}</body>\n</html>\n"
+    ))
+}
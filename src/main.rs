@@ -1,25 +1,46 @@
+mod admin;
+mod animation;
+mod backdrop;
 mod banner;
+mod cache;
 mod cli;
+mod color;
 mod db_adpater;
+mod fallback;
+mod font;
+mod gallery;
+mod manifest;
+mod negotiate;
+mod notation;
+mod report;
+mod schedule;
 mod utils;
+mod vector;
+mod watcher;
 
 use std::{
-    sync::{atomic::AtomicBool, Arc},
+    sync::{atomic::AtomicBool, Arc, RwLock},
     time::Duration,
 };
 
+use animation::AnimatedImage;
 use axum::{
     body::Body,
-    extract::{Path, Query, State},
-    http::{Response, StatusCode},
+    extract::{DefaultBodyLimit, Path, Query, State},
+    http::{header, HeaderMap, HeaderValue, Response, StatusCode},
     response::{Html, IntoResponse},
-    routing::get,
+    routing::{get, put},
     Router,
 };
-use banner::ThemeManager;
+use banner::{DynamicImageWithFormat, RenderOptions, Theme, ThemeManager};
+use cache::{RenderCache, Rendered};
 use clap::Parser;
 use cli::read_config;
+use color::ColorTransform;
 use db_adpater::DBManager;
+use image::{ImageFormat, ImageResult};
+use notation::NumberMarks;
+use schedule::Today;
 use serde::{Deserialize, Serialize};
 use tokio::{signal, time};
 
@@ -32,6 +53,81 @@ struct CountGetParams {
     theme: Option<String>,
     format: Option<String>,
     length: Option<u32>,
+    quality: Option<u8>,
+    notation: Option<String>,
+    locale: Option<String>,
+    scale: Option<f32>,
+    height: Option<u32>,
+    gap: Option<i32>,
+    padding: Option<u32>,
+    background: Option<String>,
+    background_opacity: Option<f32>,
+    border: Option<String>,
+    border_width: Option<u32>,
+    radius: Option<u32>,
+    align: Option<String>,
+    monospace: Option<bool>,
+    // svg links the glyph images of /themes/:name/:glyph
+    linked: Option<bool>,
+    hue: Option<f32>,
+    saturation: Option<f32>,
+    grayscale: Option<bool>,
+    tint: Option<String>,
+    invert: Option<bool>,
+    opacity: Option<f32>,
+    // show the count without counting
+    readonly: Option<bool>,
+}
+
+// bound of the box settings of a request, keeps images at a sane size
+const MAX_BOX_PIXELS: u32 = 256;
+const MAX_SATURATION: f32 = 10.0;
+
+impl CountGetParams {
+    fn render_options(&self, digit_count: u32, config: &cli::Config) -> RenderOptions {
+        let notation = self
+            .notation
+            .as_ref()
+            .and_then(|notation| notation.parse().ok());
+        let marks = self
+            .locale
+            .as_ref()
+            .map(|locale| NumberMarks::from_locale(locale))
+            .unwrap_or_default();
+
+        RenderOptions {
+            digits_count: digit_count,
+            pixelated: config.pixelated,
+            notation: notation.unwrap_or_default(),
+            marks,
+            scale: self.scale,
+            height: self.height,
+            max_scale: config.max_scale,
+            gap: self
+                .gap
+                .map(|gap| gap.clamp(-(MAX_BOX_PIXELS as i32), MAX_BOX_PIXELS as i32)),
+            padding: self.padding.map(|padding| padding.min(MAX_BOX_PIXELS)),
+            background: self.background.as_deref().and_then(utils::parse_color),
+            background_opacity: self.background_opacity,
+            border: self.border.as_deref().and_then(utils::parse_color),
+            border_width: self.border_width.map(|width| width.min(MAX_BOX_PIXELS)),
+            radius: self.radius.map(|radius| radius.min(MAX_BOX_PIXELS)),
+            align: self.align.as_ref().and_then(|align| align.parse().ok()),
+            monospace: self.monospace,
+            linked: self.linked.unwrap_or(false),
+            color: ColorTransform {
+                hue: self.hue.filter(|hue| hue.is_finite()),
+                saturation: self
+                    .saturation
+                    .filter(|saturation| saturation.is_finite())
+                    .map(|saturation| saturation.min(MAX_SATURATION)),
+                grayscale: self.grayscale.unwrap_or(false),
+                tint: self.tint.as_deref().and_then(utils::parse_color),
+                invert: self.invert.unwrap_or(false),
+                opacity: self.opacity.filter(|opacity| opacity.is_finite()),
+            },
+        }
+    }
 }
 
 fn internal_err(msg: &str) -> Response<Body> {
@@ -41,143 +137,416 @@ fn internal_err(msg: &str) -> Response<Body> {
         .unwrap()
 }
 
+fn encode_animated(
+    image: ImageResult<AnimatedImage>,
+    quality: Option<u8>,
+) -> Result<Rendered, &'static str> {
+    let image = image.map_err(|_| "failed to gen animated image")?;
+    let image_data = image
+        .encode(quality)
+        .map_err(|_| "failed to get animated image data")?;
+
+    Ok(Rendered {
+        content_type: image.format().to_mime_type().to_string(),
+        data: image_data.into(),
+    })
+}
+
+fn encode_raster<E>(
+    image: Result<DynamicImageWithFormat, E>,
+    quality: Option<u8>,
+) -> Result<Rendered, &'static str> {
+    let image = image.map_err(|_| "failed to gen raster image")?;
+    let image_data = image
+        .encode(quality)
+        .map_err(|_| "failed to get raster image data")?;
+
+    Ok(Rendered {
+        content_type: image.format().to_mime_type().to_string(),
+        data: image_data.into(),
+    })
+}
+
+/// Everything a counter image is rendered from.
+struct RenderRequest {
+    // of the render cache when the theme was picked
+    generation: u64,
+    theme_manager: Arc<ThemeManager>,
+    // the theme picked, loaded in `theme_manager`
+    theme_name: String,
+    number: u64,
+    format: String,
+    options: RenderOptions,
+    quality: Option<u8>,
+}
+
+impl RenderRequest {
+    fn cache_key(&self) -> String {
+        format!(
+            "{}|{}|{}|{}|{:?}|{}|{:?}",
+            self.generation,
+            self.theme_name,
+            self.number,
+            self.format,
+            self.quality,
+            self.theme_manager.dark_variant(&self.theme_name).is_some(),
+            self.options
+        )
+    }
+
+    /// Render on the blocking pool, encoding a counter takes long enough
+    /// to hold up other requests.
+    async fn render_blocking(self) -> Result<Rendered, &'static str> {
+        tokio::task::spawn_blocking(move || self.render())
+            .await
+            .unwrap_or(Err("failed to render image"))
+    }
+
+    fn render(&self) -> Result<Rendered, &'static str> {
+        let theme = self
+            .theme_manager
+            .get(&self.theme_name)
+            .map_err(|_| "theme is not loaded")?;
+        let dark = self.theme_manager.dark_variant(&self.theme_name);
+        let (number, options, quality) = (self.number, &self.options, self.quality);
+        match self.format.as_str() {
+            "webp" if theme.is_animated() => encode_animated(
+                theme.gen_animated(number, ImageFormat::WebP, options),
+                quality,
+            ),
+            "webp" => encode_raster(
+                theme.gen_raster(number, ImageFormat::WebP, options),
+                quality,
+            ),
+            "avif" => encode_raster(
+                theme.gen_raster(number, ImageFormat::Avif, options),
+                quality,
+            ),
+            "jpeg" | "jpg" => encode_raster(
+                theme.gen_raster(number, ImageFormat::Jpeg, options),
+                quality,
+            ),
+            "png" => encode_raster(theme.gen_png(number, options), quality),
+            "gif" => encode_animated(
+                theme.gen_animated(number, ImageFormat::Gif, options),
+                quality,
+            ),
+            "apng" => encode_animated(
+                theme.gen_animated(number, ImageFormat::Png, options),
+                quality,
+            ),
+            _ => {
+                let image = theme
+                    .gen_svg(number, options, dark)
+                    .map_err(|_| "failed to gen svg image")?;
+                Ok(Rendered {
+                    content_type: "image/svg+xml".to_string(),
+                    data: image.data().to_string().into(),
+                })
+            }
+        }
+    }
+}
+
+/// Render a counter. Images that depend on nothing but the request, i.e.
+/// nothing was counted, go through the render cache and get an ETag.
+async fn respond(
+    app_state: &AppState,
+    request: RenderRequest,
+    headers: &HeaderMap,
+    cacheable: bool,
+) -> Response<Body> {
+    if !cacheable {
+        return match request.render_blocking().await {
+            Ok(rendered) => image_response(rendered),
+            Err(msg) => internal_err(msg),
+        };
+    }
+
+    let key = request.cache_key();
+    let rendered = match app_state.render_cache.get(&key) {
+        Some(rendered) => rendered,
+        None => match request.render_blocking().await {
+            Ok(rendered) => {
+                app_state.render_cache.insert(key, rendered.clone());
+                rendered
+            }
+            Err(msg) => return internal_err(msg),
+        },
+    };
+
+    let etag = cache::etag(&rendered.data);
+    let if_none_match = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|if_none_match| if_none_match.to_str().ok());
+    if if_none_match.is_some_and(|if_none_match| cache::etag_matches(if_none_match, &etag)) {
+        return Response::builder()
+            .status(StatusCode::NOT_MODIFIED)
+            .header(header::ETAG, etag)
+            .body(Body::empty())
+            .unwrap();
+    }
+
+    let mut response = image_response(rendered);
+    response
+        .headers_mut()
+        .insert(header::ETAG, HeaderValue::from_str(&etag).unwrap());
+    response
+}
+
+fn image_response(rendered: Rendered) -> Response<Body> {
+    Response::builder()
+        .status(StatusCode::OK)
+        .header("Content-Type", rendered.content_type)
+        .body(Body::from(rendered.data))
+        .unwrap()
+}
+
+fn request_format(
+    params_format: Option<String>,
+    headers: &HeaderMap,
+    default_format: &str,
+    animated: bool,
+) -> String {
+    params_format.unwrap_or_else(|| {
+        let accept = headers
+            .get(header::ACCEPT)
+            .and_then(|accept| accept.to_str().ok());
+        negotiate::negotiate_format(accept, default_format, animated)
+    })
+}
+
+fn with_vary_accept(mut response: Response<Body>) -> Response<Body> {
+    response
+        .headers_mut()
+        .insert(header::VARY, HeaderValue::from_static("Accept"));
+    response
+}
+
+/// Name of the theme a request asks for, aliases resolved. Without `theme`,
+/// what a schedule shows today or `default_theme`. `random` and
+/// `random:<tag>` pick one of the loaded themes. A loaded theme is always
+/// picked by its own name, so the gallery shows every theme as it is.
+fn resolve_theme(
+    requested: Option<&str>,
+    config: &cli::Config,
+    theme_manager: &ThemeManager,
+) -> String {
+    let requested = match requested {
+        Some(theme_name) => theme_name,
+        None => schedule::scheduled_theme(&config.schedules, &Today::now())
+            .unwrap_or(&config.default_theme),
+    };
+    if theme_manager.get(requested).is_ok() {
+        return requested.to_string();
+    }
+    let requested = config
+        .theme_aliases
+        .get(requested)
+        .map(String::as_str)
+        .unwrap_or(requested);
+    let tag = match requested.strip_prefix("random") {
+        Some("") => None,
+        Some(rest) => match rest.strip_prefix(':') {
+            Some(tag) => Some(tag),
+            None => return requested.to_string(),
+        },
+        None => return requested.to_string(),
+    };
+
+    theme_manager
+        .random(&config.random_themes, tag, config.random_nsfw)
+        .unwrap_or_else(|| config.default_theme.clone())
+}
+
+/// The theme to render and its name: the requested one, or else the first
+/// loaded of `default_theme` and `fallback_themes`. `None` in strict mode or
+/// when none of them is loaded.
+fn pick_theme<'a>(
+    theme_manager: &'a ThemeManager,
+    theme_name: &str,
+    config: &cli::Config,
+) -> Option<(String, &'a Theme)> {
+    if let Ok(theme) = theme_manager.get(theme_name) {
+        return Some((theme_name.to_string(), theme));
+    }
+    if config.strict_themes {
+        return None;
+    }
+    std::iter::once(&config.default_theme)
+        .chain(&config.fallback_themes)
+        .find_map(|fallback| {
+            let theme = theme_manager.get(fallback).ok()?;
+            Some((fallback.clone(), theme))
+        })
+}
+
+/// Svg telling what went wrong, in place of a counter.
+fn error_image(status: StatusCode, msg: &str) -> Response<Body> {
+    // as much as fits the image
+    let msg: String = msg.chars().take(32).collect();
+    let svg = format!(
+        "<svg width=\"240\" height=\"40\" xmlns=\"http://www.w3.org/2000/svg\"><rect width=\"240\" height=\"40\" fill=\"#fee\" stroke=\"#c00\" /><text x=\"120\" y=\"25\" font-family=\"sans-serif\" font-size=\"14\" fill=\"#c00\" text-anchor=\"middle\">{}</text></svg>",
+        utils::escape_xml(&msg)
+    );
+    Response::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "image/svg+xml")
+        .header(header::CACHE_CONTROL, "no-store")
+        .body(Body::from(svg))
+        .unwrap()
+}
+
 async fn count(
     Path(key): Path<String>,
     Query(params): Query<CountGetParams>,
     State(app_state): State<SharedState>,
+    headers: HeaderMap,
 ) -> impl IntoResponse {
     let config = app_state.config.clone();
 
-    let request_theme = params.theme.unwrap_or(config.default_theme.clone());
-    let request_format = params.format.unwrap_or(config.default_format.clone());
     let request_len = params.length.unwrap_or(0);
     let digit_count = config.digit_count.max(request_len);
 
-    let theme_manager = &app_state.theme_manager;
-
-    let theme = theme_manager.get(&request_theme).unwrap_or(
-        theme_manager
-            .get(&config.default_theme)
-            .unwrap_or(theme_manager.get("moebooru").unwrap()),
+    let generation = app_state.render_cache.generation();
+    let theme_manager = app_state.theme_manager();
+    let request_theme = resolve_theme(params.theme.as_deref(), &config, &theme_manager);
+
+    let (theme_name, theme) = match pick_theme(&theme_manager, &request_theme, &config) {
+        Some(picked) => picked,
+        None => {
+            return error_image(
+                StatusCode::NOT_FOUND,
+                &format!("unknown theme: {request_theme}"),
+            )
+        }
+    };
+    let default_format = theme
+        .manifest()
+        .default_format
+        .as_ref()
+        .unwrap_or(&config.default_format);
+    let request_format = request_format(
+        params.format.clone(),
+        &headers,
+        default_format,
+        theme.is_animated(),
     );
 
     let db_manager = &app_state.db_manager;
-    let number = db_manager.count(&key).await.unwrap_or(0);
+    let readonly = params.readonly.unwrap_or(false);
+    let number = if readonly {
+        db_manager.peek(&key).await.unwrap_or(0)
+    } else {
+        db_manager.count(&key).await.unwrap_or(0)
+    };
 
     println!(
         "[GET] /{} | theme: {}, format: {}, length: {}, count: {}",
-        key, request_theme, request_format, digit_count, number
+        key, theme_name, request_format, digit_count, number
     );
 
-    let response = match request_format.as_str() {
-        "webp" => {
-            let image = theme.gen_webp(number, digit_count);
-            if image.is_err() {
-                return internal_err("failed to gen webp image");
-            }
-            let image = image.unwrap();
-
-            let image_data = image.encode();
-            if image_data.is_err() {
-                return internal_err("failed to get webp image data");
-            }
-
-            let image_data = image_data.unwrap();
-            Response::builder()
-                .status(StatusCode::OK)
-                .header("Content-Type", image.format().to_mime_type())
-                .body(Body::from(image_data))
-                .unwrap()
-        }
-        _ => {
-            let image = theme.gen_svg(number, digit_count, config.pixelated);
-            if image.is_err() {
-                return internal_err("failed to gen svg image");
-            }
-            let image = image.unwrap();
-            Response::builder()
-                .status(StatusCode::OK)
-                .header("Content-Type", "image/svg+xml")
-                .body(Body::from(image.data().to_string()))
-                .unwrap()
-        }
+    let request = RenderRequest {
+        generation,
+        theme_manager,
+        theme_name,
+        number,
+        format: request_format,
+        options: params.render_options(digit_count, &config),
+        quality: params.quality.map(|quality| quality.clamp(1, 100)),
     };
-
-    response
+    with_vary_accept(respond(&app_state, request, &headers, readonly).await)
 }
 
 async fn demo(
     Query(params): Query<CountGetParams>,
     State(app_state): State<SharedState>,
+    headers: HeaderMap,
 ) -> impl IntoResponse {
     let config = app_state.config.clone();
 
-    let request_theme = params.theme.unwrap_or(config.default_theme.clone());
-    let request_format = params.format.unwrap_or(config.default_format.clone());
-
     let digit_count = 10;
-    let number = 0123456789;
-
-    let theme_manager = &app_state.theme_manager;
-    let theme = theme_manager.get(&request_theme).unwrap_or(
-        theme_manager
-            .get(&config.default_theme)
-            .unwrap_or(theme_manager.get("moebooru").unwrap()),
+    let number = 123456789;
+
+    let generation = app_state.render_cache.generation();
+    let theme_manager = app_state.theme_manager();
+    let request_theme = resolve_theme(params.theme.as_deref(), &config, &theme_manager);
+    let (theme_name, theme) = match pick_theme(&theme_manager, &request_theme, &config) {
+        Some(picked) => picked,
+        None => {
+            return error_image(
+                StatusCode::NOT_FOUND,
+                &format!("unknown theme: {request_theme}"),
+            )
+        }
+    };
+    let default_format = theme
+        .manifest()
+        .default_format
+        .as_ref()
+        .unwrap_or(&config.default_format);
+    let request_format = request_format(
+        params.format.clone(),
+        &headers,
+        default_format,
+        theme.is_animated(),
     );
     println!(
-        "[GET] /{} | theme: {}, format: {}, length: {}, count: {}",
-        "demo", request_theme, request_format, digit_count, number
+        "[GET] /demo | theme: {}, format: {}, length: {}, count: {}",
+        theme_name, request_format, digit_count, number
     );
 
-    let response = match request_format.as_str() {
-        "webp" => {
-            let image = theme.gen_webp(number, digit_count);
-            if image.is_err() {
-                return internal_err("failed to gen webp image");
-            }
-            let image = image.unwrap();
-
-            let image_data = image.encode();
-            if image_data.is_err() {
-                return internal_err("failed to get webp image data");
-            }
-
-            let image_data = image_data.unwrap();
-            Response::builder()
-                .status(StatusCode::OK)
-                .header("Content-Type", image.format().to_mime_type())
-                .body(Body::from(image_data))
-                .unwrap()
-        }
-        _ => {
-            let image = theme.gen_svg(number, digit_count, config.pixelated);
-            if image.is_err() {
-                return internal_err("failed to gen svg image");
-            }
-            let image = image.unwrap();
-            Response::builder()
-                .status(StatusCode::OK)
-                .header("Content-Type", "image/svg+xml")
-                .body(Body::from(image.data().to_string()))
-                .unwrap()
-        }
+    let request = RenderRequest {
+        generation,
+        theme_manager,
+        theme_name,
+        number,
+        format: request_format,
+        options: params.render_options(digit_count, &config),
+        quality: params.quality.map(|quality| quality.clamp(1, 100)),
     };
-
-    response
+    with_vary_accept(respond(&app_state, request, &headers, true).await)
 }
 
 async fn favicon() -> impl IntoResponse {
     let favicon = Vec::from(include_bytes!("../assets/favicon.png"));
     Response::builder()
         .status(StatusCode::OK)
-        .header("Content-Type", image::ImageFormat::Png.to_mime_type())
+        .header("Content-Type", ImageFormat::Png.to_mime_type())
         .body(Body::from(favicon))
         .unwrap()
 }
 
+/// `/themes/:name/:glyph`, glyph images linked from svg. The url carries the
+/// version of the theme, so they can be cached for good.
+async fn glyph(
+    Path((theme_name, glyph_name)): Path<(String, String)>,
+    State(app_state): State<SharedState>,
+) -> impl IntoResponse {
+    let theme_manager = app_state.theme_manager();
+    let asset = theme_manager
+        .get(&theme_name)
+        .ok()
+        .and_then(|theme| theme.glyph_asset(&glyph_name));
+    match asset {
+        Some((content_type, data)) => Response::builder()
+            .status(StatusCode::OK)
+            .header(header::CONTENT_TYPE, content_type)
+            .header(header::CACHE_CONTROL, "public, max-age=31536000, immutable")
+            .body(Body::from(data))
+            .unwrap(),
+        None => Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(Body::from("no such glyph"))
+            .unwrap(),
+    }
+}
+
 struct AppState {
     config: cli::Config,
-    theme_manager: ThemeManager,
+    // swapped as a whole when themes_dir changes
+    theme_manager: RwLock<Arc<ThemeManager>>,
+    render_cache: RenderCache,
     db_manager: DBManager,
     should_exit: AtomicBool,
 }
@@ -185,24 +554,70 @@ struct AppState {
 impl AppState {
     fn new(config: cli::Config, theme_manager: ThemeManager, db_manager: DBManager) -> Self {
         AppState {
+            render_cache: RenderCache::new(config.render_cache_size),
             config,
-            theme_manager,
+            theme_manager: RwLock::new(Arc::new(theme_manager)),
             db_manager,
             should_exit: AtomicBool::new(false),
         }
     }
+
+    fn theme_manager(&self) -> Arc<ThemeManager> {
+        self.theme_manager.read().unwrap().clone()
+    }
+
+    fn set_theme_manager(&self, theme_manager: ThemeManager) {
+        *self.theme_manager.write().unwrap() = Arc::new(theme_manager);
+        self.render_cache.invalidate();
+    }
+
+    /// Change a copy of the loaded themes and swap it in.
+    fn update_theme_manager(&self, update: impl FnOnce(&mut ThemeManager)) {
+        let mut current = self.theme_manager.write().unwrap();
+        let mut theme_manager = current.as_ref().clone();
+        update(&mut theme_manager);
+        *current = Arc::new(theme_manager);
+        self.render_cache.invalidate();
+    }
 }
 
 type SharedState = Arc<AppState>;
 
+/// `themes check`, the exit code is 1 if any theme is broken.
+fn check_themes(themes_dir: &str) -> i32 {
+    let reports = match ThemeManager::check(themes_dir) {
+        Ok(reports) => reports,
+        Err(e) => {
+            println!("[Error] unable to read {}: {}", themes_dir, e.kind());
+            return 2;
+        }
+    };
+
+    println!("Themes in {}:", themes_dir);
+    for report in &reports {
+        print!("{}", report);
+    }
+    if reports.iter().any(|report| report.is_broken()) {
+        return 1;
+    }
+    0
+}
+
 #[tokio::main]
 async fn main() {
     // cli args parase
     let args = cli::CliArgs::parse();
+    if let Some(cli::Command::Themes {
+        command: cli::ThemesCommand::Check { themes_dir },
+    }) = &args.command
+    {
+        std::process::exit(check_themes(themes_dir));
+    }
     let cfg = read_config(&args.config_path);
 
     // init
     let theme_manager = ThemeManager::new(&cfg.themes_dir).expect("failed to load themes");
+    print!("{}", theme_manager);
 
     let mut db_manager = DBManager::new(db_adpater::SqliteClient::new(
         &cfg.sqlite.path,
@@ -223,6 +638,15 @@ async fn main() {
         .route("/status", get(status))
         .route("/favicon.ico", get(favicon))
         .route("/demo", get(demo))
+        .route("/themes", get(gallery::themes_page))
+        .route("/themes/:name/:glyph", get(glyph))
+        .route("/api/themes", get(gallery::api_themes))
+        .route(
+            "/admin/themes/:name",
+            put(admin::upload_theme)
+                .delete(admin::delete_theme)
+                .layer(DefaultBodyLimit::max(cfg.max_theme_size)),
+        )
         .route("/:key", get(count))
         .with_state(shared_state.clone());
     let listener = tokio::net::TcpListener::bind(format!("{}:{}", cfg.listen, cfg.port))
@@ -247,6 +671,34 @@ async fn main() {
         }
     });
 
+    // register a task reloading themes when themes_dir changes
+    let local_state = shared_state.clone();
+    let themes_watcher = if cfg.watch_themes {
+        watcher::watch(&cfg.themes_dir)
+            .inspect_err(|e| println!("[Warn] unable to watch {}: {}", cfg.themes_dir, e))
+            .ok()
+    } else {
+        None
+    };
+    let reload_themes_handle = themes_watcher.map(|(themes_watcher, mut changes)| {
+        tokio::spawn(async move {
+            // dropping the watcher stops the changes
+            let _themes_watcher = themes_watcher;
+            while watcher::changed(&mut changes).await {
+                let theme_manager = local_state.theme_manager();
+                let reloaded = tokio::task::spawn_blocking(move || theme_manager.reload()).await;
+                match reloaded {
+                    Ok(Ok(theme_manager)) => {
+                        println!("[Info] reload themes");
+                        print!("{}", theme_manager);
+                        local_state.set_theme_manager(theme_manager);
+                    }
+                    _ => println!("[Warn] unable to reload themes, keep the loaded ones"),
+                }
+            }
+        })
+    });
+
     println!("listen on: http://{}:{}", cfg.listen, cfg.port);
     axum::serve(listener, app)
         .with_graceful_shutdown(shutdown_signal(shared_state.clone()))
@@ -255,6 +707,9 @@ async fn main() {
 
     // cancel timer job
     sync_to_backend_handle.abort();
+    if let Some(reload_themes_handle) = reload_themes_handle {
+        reload_themes_handle.abort();
+    }
 
     println!("[Shutdown]")
 }
@@ -279,3 +734,100 @@ async fn shutdown_signal(app_state: SharedState) {
         .store(true, std::sync::atomic::Ordering::Relaxed);
     let _ = app_state.db_manager.sync_to_backend().await;
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use banner::ThemeSource;
+
+    // a themes_dir holding svg themes of the given names, removed on drop
+    struct ThemesDir(std::path::PathBuf);
+
+    impl ThemesDir {
+        fn new(test_name: &str, theme_names: &[&str]) -> Self {
+            let dir = std::env::temp_dir()
+                .join(format!("moe-counter-{test_name}-{}", std::process::id()));
+            for theme_name in theme_names {
+                let theme_dir = dir.join(theme_name);
+                std::fs::create_dir_all(&theme_dir).unwrap();
+                for digit in 0..10 {
+                    std::fs::write(
+                        theme_dir.join(format!("{digit}.svg")),
+                        "<svg width=\"8\" height=\"16\" xmlns=\"http://www.w3.org/2000/svg\"><rect width=\"8\" height=\"16\" /></svg>",
+                    )
+                    .unwrap();
+                }
+            }
+            ThemesDir(dir)
+        }
+
+        fn theme_manager(&self) -> ThemeManager {
+            ThemeManager::new(self.0.to_str().unwrap()).unwrap()
+        }
+    }
+
+    impl Drop for ThemesDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn dark_variant_of_fallback_theme() {
+        let themes_dir = ThemesDir::new("dark-fallback", &["night", "night-dark"]);
+        let theme_manager = themes_dir.theme_manager();
+        let config = cli::Config {
+            default_theme: "night".to_string(),
+            ..Default::default()
+        };
+
+        let (theme_name, _) = pick_theme(&theme_manager, "missing", &config).unwrap();
+        assert_eq!(theme_name, "night");
+        assert!(theme_manager.dark_variant(&theme_name).is_some());
+
+        let (theme_name, _) = pick_theme(&theme_manager, "night-dark", &config).unwrap();
+        assert!(theme_manager.dark_variant(&theme_name).is_none());
+    }
+
+    #[test]
+    fn reload_keeps_broken_override() {
+        let themes_dir = ThemesDir::new("broken-override", &["moebooru"]);
+        let theme_manager = themes_dir.theme_manager();
+        let source = |theme_manager: &ThemeManager| theme_manager.get("moebooru").unwrap().source();
+        assert_eq!(source(&theme_manager), ThemeSource::External);
+
+        // half copied
+        std::fs::remove_file(themes_dir.0.join("moebooru").join("3.svg")).unwrap();
+        let theme_manager = theme_manager.reload().unwrap();
+        assert_eq!(source(&theme_manager), ThemeSource::External);
+        let theme_manager = theme_manager.reload().unwrap();
+        assert_eq!(source(&theme_manager), ThemeSource::External);
+
+        std::fs::remove_dir_all(themes_dir.0.join("moebooru")).unwrap();
+        let theme_manager = theme_manager.reload().unwrap();
+        assert_eq!(source(&theme_manager), ThemeSource::Embedded);
+    }
+
+    #[test]
+    fn loaded_themes_go_by_their_own_name() {
+        let themes_dir = ThemesDir::new("own-name", &["booru", "random", "random:cute"]);
+        let theme_manager = themes_dir.theme_manager();
+        let config = cli::Config {
+            theme_aliases: HashMap::from([
+                ("booru".to_string(), "moebooru".to_string()),
+                ("neko".to_string(), "asoul".to_string()),
+            ]),
+            random_themes: vec!["moebooru".to_string()],
+            ..Default::default()
+        };
+        let resolve = |theme_name| resolve_theme(Some(theme_name), &config, &theme_manager);
+
+        for theme_name in ["booru", "random", "random:cute"] {
+            assert_eq!(resolve(theme_name), theme_name);
+        }
+        assert_eq!(resolve("neko"), "asoul");
+        assert_eq!(resolve("random:sfw"), "moebooru");
+    }
+}
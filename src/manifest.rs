@@ -0,0 +1,228 @@
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{banner::Glyph, utils};
+
+pub const MANIFEST_FILE_NAME: &str = "theme.toml";
+
+/// Optional `theme.toml` next to the digit images of a theme.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ThemeManifest {
+    pub display_name: Option<String>,
+    pub author: Option<String>,
+    pub license: Option<String>,
+    pub credit_url: Option<String>,
+    pub nsfw: bool,
+    // free-form labels `theme=random:<tag>` can pick by
+    pub tags: Vec<String>,
+    pub default_format: Option<String>,
+    // pixels between two digits, negative values overlap them
+    #[serde(alias = "spacing")]
+    pub gap: i32,
+    pub scale: f32,
+    pub pixelated: Option<bool>,
+    pub padding: u32,
+    // hex colours like `ff8800` or `ff880080`
+    pub background: Option<String>,
+    pub border: Option<String>,
+    pub border_width: Option<u32>,
+    pub radius: u32,
+    // theme shown instead in svg when the viewer prefers a dark colour scheme
+    pub dark_variant: Option<String>,
+    pub sprite: Option<SpriteSheet>,
+    pub font: Option<FontGlyphs>,
+    // where glyphs lower than the tallest one sit
+    pub align: Align,
+    // centre every digit in a cell as wide as the widest digit
+    pub monospace: bool,
+}
+
+impl Default for ThemeManifest {
+    fn default() -> Self {
+        ThemeManifest {
+            display_name: None,
+            author: None,
+            license: None,
+            credit_url: None,
+            nsfw: false,
+            tags: Vec::new(),
+            default_format: None,
+            gap: 0,
+            scale: 1.0,
+            pixelated: None,
+            padding: 0,
+            background: None,
+            border: None,
+            border_width: None,
+            radius: 0,
+            dark_variant: None,
+            sprite: None,
+            font: None,
+            align: Align::Top,
+            monospace: false,
+        }
+    }
+}
+
+/// Vertical alignment of glyphs of different heights.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Align {
+    #[default]
+    Top,
+    Middle,
+    // the bottom edges line up like a baseline
+    #[serde(alias = "baseline")]
+    Bottom,
+}
+
+impl FromStr for Align {
+    type Err = std::io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "top" => Ok(Align::Top),
+            "middle" => Ok(Align::Middle),
+            "bottom" | "baseline" => Ok(Align::Bottom),
+            _ => Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, s)),
+        }
+    }
+}
+
+/// One image holding the glyphs of a theme in cells of the same size,
+/// read left to right and top to bottom.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct SpriteSheet {
+    pub file: String,
+    // 0 splits a single row evenly between the glyphs
+    pub cell_width: u32,
+    pub cell_height: u32,
+    // glyph of every cell, an empty name skips a cell
+    pub glyphs: Vec<String>,
+}
+
+impl SpriteSheet {
+    /// Size of a cell in a sheet of this size.
+    pub fn cell_size(&self, sheet_width: u32, sheet_height: u32) -> (u32, u32) {
+        match self.cell_width {
+            0 => (sheet_width / self.glyphs.len().max(1) as u32, sheet_height),
+            cell_width => match self.cell_height {
+                0 => (cell_width, sheet_height),
+                cell_height => (cell_width, cell_height),
+            },
+        }
+    }
+}
+
+impl Default for SpriteSheet {
+    fn default() -> Self {
+        SpriteSheet {
+            file: "sprite.png".to_string(),
+            cell_width: 0,
+            cell_height: 0,
+            glyphs: (0..10).map(|digit| digit.to_string()).collect(),
+        }
+    }
+}
+
+/// Glyphs written with a font file of the theme instead of drawn as images.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct FontGlyphs {
+    pub file: String,
+    // font size in pixels
+    pub size: f32,
+    pub color: String,
+    pub stroke: Option<String>,
+    pub stroke_width: f32,
+    // text of the end caps, none if not set
+    pub prefix: Option<String>,
+    pub suffix: Option<String>,
+}
+
+impl Default for FontGlyphs {
+    fn default() -> Self {
+        FontGlyphs {
+            file: "font.ttf".to_string(),
+            size: 48.0,
+            color: "000000".to_string(),
+            stroke: None,
+            stroke_width: 0.0,
+            prefix: None,
+            suffix: None,
+        }
+    }
+}
+
+impl ThemeManifest {
+    /// `sfw` and `nsfw` follow the `nsfw` flag, other tags are listed.
+    pub fn has_tag(&self, tag: &str) -> bool {
+        match tag {
+            "sfw" => !self.nsfw,
+            "nsfw" => self.nsfw,
+            _ => self.tags.iter().any(|own| own == tag),
+        }
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Box<dyn std::error::Error>> {
+        let manifest: ThemeManifest = toml::from_str(std::str::from_utf8(bytes)?)?;
+        if !manifest.scale.is_finite() || manifest.scale <= 0.0 {
+            return Err(Box::new(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "scale must be a positive number",
+            )));
+        }
+        for color in [&manifest.background, &manifest.border]
+            .into_iter()
+            .flatten()
+        {
+            if utils::parse_color(color).is_none() {
+                return Err(Box::new(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("invalid colour: {color}"),
+                )));
+            }
+        }
+        if let Some(font) = &manifest.font {
+            if !font.size.is_finite() || font.size <= 0.0 || font.size > 1024.0 {
+                return Err(Box::new(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    "font size must be a number from 0 to 1024",
+                )));
+            }
+            if !font.stroke_width.is_finite() || font.stroke_width < 0.0 {
+                return Err(Box::new(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    "font stroke_width must not be negative",
+                )));
+            }
+            for color in [Some(&font.color), font.stroke.as_ref()]
+                .into_iter()
+                .flatten()
+            {
+                if utils::parse_color(color).is_none() {
+                    return Err(Box::new(std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        format!("invalid colour: {color}"),
+                    )));
+                }
+            }
+        }
+        if let Some(sprite) = &manifest.sprite {
+            let unknown = sprite
+                .glyphs
+                .iter()
+                .find(|name| !name.is_empty() && Glyph::from_name(name).is_none());
+            if let Some(name) = unknown {
+                return Err(Box::new(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("unknown glyph in sprite: {name}"),
+                )));
+            }
+        }
+        Ok(manifest)
+    }
+}
@@ -0,0 +1,79 @@
+// formats we are able to serve in the order we prefer them, and whether
+// they keep the animation of an animated theme. avif is slow to encode, so
+// it is only picked when asked for with a higher quality than the others
+const SUPPORTED_FORMATS: [(&str, &str, bool); 5] = [
+    ("image/webp", "webp", true),
+    ("image/svg+xml", "svg", true),
+    ("image/png", "png", false),
+    ("image/jpeg", "jpeg", false),
+    ("image/avif", "avif", false),
+];
+
+struct MediaRange<'a> {
+    mime: &'a str,
+    quality: f32,
+}
+
+fn parse_accept(accept: &str) -> Vec<MediaRange<'_>> {
+    accept
+        .split(',')
+        .filter_map(|item| {
+            let mut parts = item.split(';');
+            let mime = parts.next()?.trim();
+            if mime.is_empty() {
+                return None;
+            }
+
+            let mut quality = 1.0;
+            for param in parts {
+                if let Some(value) = param.trim().strip_prefix("q=") {
+                    quality = value.trim().parse().unwrap_or(0.0);
+                }
+            }
+
+            Some(MediaRange { mime, quality })
+        })
+        .collect()
+}
+
+/// Pick the output format for a request that did not ask for one.
+///
+/// Only formats the client names explicitly are considered, a client that
+/// just sends wildcards gets the configured default format. For animated
+/// themes, formats that would drop the animation are skipped.
+pub fn negotiate_format(accept: Option<&str>, default_format: &str, animated: bool) -> String {
+    let accept = match accept {
+        Some(accept) => accept,
+        None => return default_format.to_string(),
+    };
+
+    let ranges = parse_accept(accept);
+
+    let mut best: Option<(&str, f32)> = None;
+    for (mime, format, keeps_animation) in SUPPORTED_FORMATS {
+        if animated && !keeps_animation {
+            continue;
+        }
+
+        let quality = ranges
+            .iter()
+            .filter(|range| range.mime.eq_ignore_ascii_case(mime))
+            .map(|range| range.quality)
+            .reduce(f32::max);
+
+        let quality = match quality {
+            Some(quality) if quality > 0.0 => quality,
+            _ => continue,
+        };
+
+        // on a tie the earlier, preferred format wins
+        if best.map_or(true, |(_, best_quality)| quality > best_quality) {
+            best = Some((format, quality));
+        }
+    }
+
+    match best {
+        Some((format, _)) => format.to_string(),
+        None => default_format.to_string(),
+    }
+}
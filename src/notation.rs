@@ -0,0 +1,181 @@
+use std::str::FromStr;
+
+use crate::{banner::Glyph, utils};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Notation {
+    // 1234567
+    #[default]
+    Plain,
+    // 1,234,567
+    Grouped,
+    // 1.2M
+    Compact,
+}
+
+impl FromStr for Notation {
+    type Err = std::io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "plain" => Ok(Notation::Plain),
+            "grouped" => Ok(Notation::Grouped),
+            "compact" => Ok(Notation::Compact),
+            _ => Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, s)),
+        }
+    }
+}
+
+/// Thousands separator and decimal mark of a locale.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NumberMarks {
+    pub group: Glyph,
+    pub decimal: Glyph,
+}
+
+impl Default for NumberMarks {
+    fn default() -> Self {
+        NumberMarks {
+            group: Glyph::Comma,
+            decimal: Glyph::Dot,
+        }
+    }
+}
+
+impl NumberMarks {
+    /// Marks of a locale such as `en`, `de-DE` or `fr_FR`, only the
+    /// language is looked at. Unknown languages get `1,234.5`.
+    pub fn from_locale(locale: &str) -> Self {
+        let language = locale
+            .split(['-', '_'])
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+
+        match language.as_str() {
+            "de" | "es" | "it" | "nl" | "pt" | "id" | "tr" | "da" | "el" => NumberMarks {
+                group: Glyph::Dot,
+                decimal: Glyph::Comma,
+            },
+            "fr" | "ru" | "pl" | "cs" | "sk" | "sv" | "fi" | "nb" | "no" | "uk" | "hu" => {
+                NumberMarks {
+                    group: Glyph::Space,
+                    decimal: Glyph::Comma,
+                }
+            }
+            _ => NumberMarks::default(),
+        }
+    }
+}
+
+const COMPACT_UNITS: [(u64, Glyph); 4] = [
+    (1_000_000_000_000, Glyph::Trillion),
+    (1_000_000_000, Glyph::Billion),
+    (1_000_000, Glyph::Million),
+    (1_000, Glyph::Thousand),
+];
+
+fn digits_of(number: u64, digits_count: u32) -> impl Iterator<Item = Glyph> {
+    utils::u64_to_digit(number, digits_count)
+        .into_iter()
+        .map(Glyph::Digit)
+}
+
+/// Spell a number as the glyphs to draw.
+///
+/// `digits_count` zero pads plain and grouped numbers, compact numbers are
+/// never padded.
+pub fn to_glyphs(
+    number: u64,
+    digits_count: u32,
+    notation: Notation,
+    marks: NumberMarks,
+) -> Vec<Glyph> {
+    match notation {
+        Notation::Plain => digits_of(number, digits_count).collect(),
+        Notation::Grouped => {
+            let digits: Vec<Glyph> = digits_of(number, digits_count).collect();
+            let mut glyphs = Vec::new();
+            for (idx, digit) in digits.iter().enumerate() {
+                if idx > 0 && (digits.len() - idx) % 3 == 0 {
+                    glyphs.push(marks.group);
+                }
+                glyphs.push(*digit);
+            }
+            glyphs
+        }
+        Notation::Compact => {
+            let unit = COMPACT_UNITS.iter().find(|(size, _)| number >= *size);
+            let (size, unit) = match unit {
+                Some(unit) => *unit,
+                None => return digits_of(number, 0).collect(),
+            };
+
+            // truncate instead of rounding, so 999999 never shows as 1000.0k
+            let tenths = (number as u128 * 10 / size as u128) as u64;
+            let (integer, fraction) = (tenths / 10, tenths % 10);
+
+            let mut glyphs: Vec<Glyph> = digits_of(integer, 0).collect();
+            if integer < 100 && fraction != 0 {
+                glyphs.push(marks.decimal);
+                glyphs.push(Glyph::Digit(fraction as u32));
+            }
+            glyphs.push(unit);
+            glyphs
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // glyphs written out the way the notation reads
+    fn spell(number: u64, digits_count: u32, notation: Notation, locale: &str) -> String {
+        to_glyphs(
+            number,
+            digits_count,
+            notation,
+            NumberMarks::from_locale(locale),
+        )
+        .into_iter()
+        .map(|glyph| match glyph {
+            Glyph::Digit(digit) => char::from_digit(digit, 10).unwrap(),
+            Glyph::Comma => ',',
+            Glyph::Dot => '.',
+            Glyph::Space => ' ',
+            Glyph::Thousand => 'k',
+            Glyph::Million => 'M',
+            Glyph::Billion => 'B',
+            Glyph::Trillion => 'T',
+            Glyph::Prefix | Glyph::Suffix => '?',
+        })
+        .collect()
+    }
+
+    #[test]
+    fn grouped() {
+        assert_eq!(spell(1234567, 0, Notation::Grouped, "en"), "1,234,567");
+        assert_eq!(spell(1234567, 0, Notation::Grouped, "de"), "1.234.567");
+        assert_eq!(spell(123, 0, Notation::Grouped, "en"), "123");
+        assert_eq!(spell(1234, 7, Notation::Grouped, "fr"), "0 001 234");
+    }
+
+    #[test]
+    fn compact() {
+        // truncated, never rounded up to 1000k
+        assert_eq!(spell(999950, 0, Notation::Compact, "en"), "999k");
+        assert_eq!(spell(1250, 0, Notation::Compact, "en"), "1.2k");
+        assert_eq!(spell(1250, 0, Notation::Compact, "de"), "1,2k");
+        assert_eq!(spell(3_000_000, 0, Notation::Compact, "en"), "3M");
+        assert_eq!(spell(999, 5, Notation::Compact, "en"), "999");
+    }
+
+    #[test]
+    fn zero() {
+        assert_eq!(spell(0, 0, Notation::Plain, "en"), "0");
+        assert_eq!(spell(0, 0, Notation::Grouped, "en"), "0");
+        assert_eq!(spell(0, 0, Notation::Compact, "en"), "0");
+        assert_eq!(spell(0, 4, Notation::Grouped, "en"), "0,000");
+    }
+}
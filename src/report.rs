@@ -0,0 +1,143 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+
+use crate::banner::Glyph;
+
+/// What loading a theme found, shown at startup and by `themes check`.
+#[derive(Debug, Clone, Default)]
+pub struct ThemeReport {
+    pub name: String,
+    pub missing_digits: Vec<u32>,
+    // file name and why it was left out
+    pub bad_files: Vec<(String, String)>,
+    // files not named after a glyph
+    pub unknown_files: Vec<String>,
+    pub bad_manifest: Option<String>,
+    pub formats: BTreeSet<String>,
+    pub digit_heights: BTreeSet<u32>,
+    // frames of every animated glyph by file name
+    pub frame_counts: BTreeMap<String, usize>,
+    // anything else worth fixing, e.g. a broken theme overriding this one
+    pub warnings: Vec<String>,
+}
+
+impl ThemeReport {
+    pub fn new(name: &str) -> Self {
+        ThemeReport {
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub fn add_glyph(
+        &mut self,
+        file_name: &str,
+        glyph: Glyph,
+        format: &str,
+        height: u32,
+        frames: usize,
+    ) {
+        self.formats.insert(format.to_string());
+        if glyph.is_digit() {
+            self.digit_heights.insert(height);
+        }
+        if frames > 1 {
+            self.frame_counts.insert(file_name.to_string(), frames);
+        }
+    }
+
+    /// Note that a broken theme in `themes_dir` failed to override the
+    /// embedded theme of this report, with what is wrong with it.
+    pub fn add_failed_override(&mut self, external: &ThemeReport) {
+        self.warnings
+            .push("external override failed, using embedded".to_string());
+        for problem in external.problems() {
+            self.warnings
+                .push(format!("external override: {}", problem));
+        }
+    }
+
+    /// A broken theme is not loaded.
+    pub fn is_broken(&self) -> bool {
+        !self.missing_digits.is_empty()
+    }
+
+    /// Everything worth fixing, broken or not.
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if !self.missing_digits.is_empty() {
+            let digits: Vec<String> = self
+                .missing_digits
+                .iter()
+                .map(|digit| digit.to_string())
+                .collect();
+            problems.push(format!("missing digits: {}", digits.join(", ")));
+        }
+        for (file_name, reason) in &self.bad_files {
+            problems.push(format!("bad file {}: {}", file_name, reason));
+        }
+        if !self.unknown_files.is_empty() {
+            problems.push(format!("unknown files: {}", self.unknown_files.join(", ")));
+        }
+        if let Some(reason) = &self.bad_manifest {
+            problems.push(format!("bad manifest: {}", reason));
+        }
+        if self.digit_heights.len() > 1 {
+            let heights: Vec<String> = self
+                .digit_heights
+                .iter()
+                .map(|height| height.to_string())
+                .collect();
+            problems.push(format!("mixed digit heights: {}", heights.join(", ")));
+        }
+        if self.formats.len() > 1 {
+            let formats: Vec<&str> = self.formats.iter().map(String::as_str).collect();
+            problems.push(format!("mixed formats: {}", formats.join(", ")));
+        }
+        problems.extend(self.warnings.iter().cloned());
+        problems
+    }
+
+    fn status(&self) -> &'static str {
+        if self.is_broken() {
+            "broken"
+        } else if self.problems().is_empty() {
+            "ok"
+        } else {
+            "warn"
+        }
+    }
+
+    /// Lines below the name of the theme, problems first.
+    pub fn details(&self) -> Vec<String> {
+        let mut details = self.problems();
+        if !self.frame_counts.is_empty() {
+            let frame_counts: Vec<String> = self
+                .frame_counts
+                .iter()
+                .map(|(file_name, frames)| format!("{}={}", file_name, frames))
+                .collect();
+            details.push(format!("frames: {}", frame_counts.join(", ")));
+        }
+        details
+    }
+}
+
+impl fmt::Display for ThemeReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let formats: Vec<&str> = self.formats.iter().map(String::as_str).collect();
+        writeln!(
+            f,
+            "  {}: {} ({})",
+            self.name,
+            self.status(),
+            formats.join(", ")
+        )?;
+        for detail in self.details() {
+            writeln!(f, "    {}", detail)?;
+        }
+        Ok(())
+    }
+}
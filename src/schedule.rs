@@ -0,0 +1,214 @@
+use std::{
+    fmt,
+    str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+/// A day of every year, written `MM-DD` in config.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(try_from = "String", into = "String")]
+pub struct MonthDay {
+    pub month: u32,
+    pub day: u32,
+}
+
+impl FromStr for MonthDay {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid date, expected MM-DD: {s}");
+        let (month, day) = s.split_once('-').ok_or_else(invalid)?;
+        let month: u32 = month.parse().map_err(|_| invalid())?;
+        let day: u32 = day.parse().map_err(|_| invalid())?;
+        // february 29 is a day of leap years only, still a valid day
+        let days_in_month = match month {
+            2 => 29,
+            4 | 6 | 9 | 11 => 30,
+            1..=12 => 31,
+            _ => return Err(invalid()),
+        };
+        if !(1..=days_in_month).contains(&day) {
+            return Err(invalid());
+        }
+        Ok(MonthDay { month, day })
+    }
+}
+
+impl TryFrom<String> for MonthDay {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<MonthDay> for String {
+    fn from(value: MonthDay) -> Self {
+        value.to_string()
+    }
+}
+
+impl fmt::Display for MonthDay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02}-{:02}", self.month, self.day)
+    }
+}
+
+/// A day in UTC, what schedules are checked against.
+#[derive(Debug, Clone, Copy)]
+pub struct Today {
+    // days since 1970-01-01
+    pub days: u64,
+    pub date: MonthDay,
+}
+
+impl Today {
+    pub fn now() -> Self {
+        let secs = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|since| since.as_secs())
+            .unwrap_or_default();
+        Self::from_days(secs / 86400)
+    }
+
+    fn from_days(days: u64) -> Self {
+        // civil date from days, after Howard Hinnant's `civil_from_days`
+        let z = days + 719468;
+        let doe = z % 146097;
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+
+        Today {
+            days,
+            date: MonthDay { month, day },
+        }
+    }
+}
+
+/// Themes shown instead of `default_theme` on some days of the year.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct Schedule {
+    // one a day in turn when several, `random` picks every time
+    pub themes: Vec<String>,
+    // first and last day, both included, the whole year when left out
+    pub from: Option<MonthDay>,
+    pub to: Option<MonthDay>,
+}
+
+impl Schedule {
+    pub fn is_active(&self, today: &Today) -> bool {
+        let from = self.from.unwrap_or(MonthDay { month: 1, day: 1 });
+        let to = self.to.unwrap_or(MonthDay { month: 12, day: 31 });
+        if from <= to {
+            from <= today.date && today.date <= to
+        } else {
+            // over new year, e.g. 12-20 to 01-06
+            from <= today.date || today.date <= to
+        }
+    }
+
+    pub fn theme_of(&self, today: &Today) -> Option<&str> {
+        if self.themes.is_empty() {
+            return None;
+        }
+        let idx = today.days % self.themes.len() as u64;
+        Some(&self.themes[idx as usize])
+    }
+}
+
+/// Theme of the first schedule active today.
+pub fn scheduled_theme<'a>(schedules: &'a [Schedule], today: &Today) -> Option<&'a str> {
+    schedules
+        .iter()
+        .filter(|schedule| schedule.is_active(today))
+        .find_map(|schedule| schedule.theme_of(today))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(s: &str) -> MonthDay {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parse_month_day() {
+        assert_eq!(date("12-31"), MonthDay { month: 12, day: 31 });
+        assert_eq!(date("02-29"), MonthDay { month: 2, day: 29 });
+        assert_eq!(date("2-3").to_string(), "02-03");
+        for invalid in [
+            "02-30", "02-31", "04-31", "11-31", "00-10", "13-01", "01-00", "0131", "",
+        ] {
+            assert!(invalid.parse::<MonthDay>().is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn today_from_days() {
+        assert_eq!(Today::from_days(0).date, date("01-01"));
+        assert_eq!(Today::from_days(364).date, date("12-31"));
+        assert_eq!(Today::from_days(365).date, date("01-01"));
+        // leap days, 2000 is a leap year as a multiple of 400
+        assert_eq!(Today::from_days(11016).date, date("02-29"));
+        assert_eq!(Today::from_days(19782).date, date("02-29"));
+        assert_eq!(Today::from_days(19783).date, date("03-01"));
+        assert_eq!(Today::from_days(19722).date, date("12-31"));
+    }
+
+    #[test]
+    fn schedule_over_new_year() {
+        let schedule = Schedule {
+            themes: vec!["snow".to_string()],
+            from: Some(date("12-20")),
+            to: Some(date("01-06")),
+        };
+        let on = |s: &str| Today {
+            days: 0,
+            date: date(s),
+        };
+        for active in ["12-20", "12-31", "01-01", "01-06"] {
+            assert!(schedule.is_active(&on(active)), "{active}");
+        }
+        for inactive in ["12-19", "01-07", "06-15"] {
+            assert!(!schedule.is_active(&on(inactive)), "{inactive}");
+        }
+    }
+
+    #[test]
+    fn theme_rotation() {
+        let schedule = Schedule {
+            themes: vec!["a".to_string(), "b".to_string(), "c".to_string()],
+            ..Default::default()
+        };
+        let themes: Vec<&str> = (19782..19787)
+            .map(|days| schedule.theme_of(&Today::from_days(days)).unwrap())
+            .collect();
+        // 19782 is a multiple of 3
+        assert_eq!(themes, ["a", "b", "c", "a", "b"]);
+        assert!(Schedule::default().theme_of(&Today::from_days(0)).is_none());
+
+        let schedules = [
+            Schedule {
+                themes: vec!["first".to_string()],
+                from: Some(date("03-01")),
+                to: Some(date("03-31")),
+            },
+            schedule,
+        ];
+        assert_eq!(
+            scheduled_theme(&schedules, &Today::from_days(19783)),
+            Some("first")
+        );
+        assert_eq!(
+            scheduled_theme(&schedules, &Today::from_days(19782)),
+            Some("a")
+        );
+    }
+}
@@ -1,3 +1,12 @@
+use image::{
+    error::{EncodingError, ImageFormatHint},
+    ImageError, ImageFormat, ImageResult, Rgb, RgbImage, Rgba, RgbaImage,
+};
+use resvg::tiny_skia;
+
+// quality of lossy formats when the request does not ask for one
+pub const DEFAULT_QUALITY: u8 = 80;
+
 pub fn u64_to_digit(number: u64, digit_count: u32) -> Vec<u32> {
     let mut number = number.to_string();
     let number_digits = number.len() as u32;
@@ -7,8 +16,96 @@ pub fn u64_to_digit(number: u64, digit_count: u32) -> Vec<u32> {
         number.insert(0, '0');
     }
 
-    number
-        .chars()
-        .map(|c| c.to_digit(10).unwrap() as u32)
-        .collect()
+    number.chars().map(|c| c.to_digit(10).unwrap()).collect()
+}
+
+/// Lossy WebP through libwebp, image-webp can only write lossless images.
+pub fn encode_webp_lossy(image: &RgbaImage, quality: u8) -> ImageResult<Vec<u8>> {
+    let encoder = webp::Encoder::from_rgba(image.as_raw(), image.width(), image.height());
+    // libwebp refuses images over 16383 pixels on a side among others
+    let encoded = encoder.encode_simple(false, quality as f32).map_err(|e| {
+        ImageError::Encoding(EncodingError::new(
+            ImageFormatHint::Exact(ImageFormat::WebP),
+            format!("{e:?}"),
+        ))
+    })?;
+    Ok(encoded.to_vec())
+}
+
+/// Drop the alpha channel for formats without one by blending onto white.
+pub fn flatten_alpha(image: &RgbaImage) -> RgbImage {
+    RgbImage::from_fn(image.width(), image.height(), |x, y| {
+        let [r, g, b, a] = image.get_pixel(x, y).0;
+        let blend = |c: u8| ((c as u32 * a as u32 + 255 * (255 - a as u32)) / 255) as u8;
+        Rgb([blend(r), blend(g), blend(b)])
+    })
+}
+
+/// Convert a resvg render target, tiny-skia keeps premultiplied alpha.
+pub fn pixmap_to_rgba(pixmap: &tiny_skia::Pixmap) -> RgbaImage {
+    let mut image = RgbaImage::new(pixmap.width(), pixmap.height());
+    for (dst, src) in image.pixels_mut().zip(pixmap.pixels()) {
+        let color = src.demultiply();
+        *dst = Rgba([color.red(), color.green(), color.blue(), color.alpha()]);
+    }
+    image
+}
+
+/// A colour as svg attributes, e.g. ` fill="#ff8800" fill-opacity="1.000"`.
+pub fn svg_paint(attribute: &str, color: Rgba<u8>) -> String {
+    let [r, g, b, a] = color.0;
+    format!(
+        " {attribute}=\"#{r:02x}{g:02x}{b:02x}\" {attribute}-opacity=\"{:.3}\"",
+        a as f32 / 255.0
+    )
+}
+
+/// Parse a hex colour like `f80`, `ff8800` or `ff880080`, the leading `#`
+/// is optional.
+pub fn parse_color(color: &str) -> Option<Rgba<u8>> {
+    let hex = color.strip_prefix('#').unwrap_or(color);
+    if !hex.is_ascii() {
+        return None;
+    }
+
+    let channels: Vec<u8> = match hex.len() {
+        3 | 4 => hex
+            .chars()
+            .map(|c| c.to_digit(16).map(|v| v as u8 * 17))
+            .collect::<Option<_>>()?,
+        6 | 8 => (0..hex.len())
+            .step_by(2)
+            .map(|idx| u8::from_str_radix(&hex[idx..idx + 2], 16).ok())
+            .collect::<Option<_>>()?,
+        _ => return None,
+    };
+
+    Some(Rgba([
+        channels[0],
+        channels[1],
+        channels[2],
+        channels.get(3).copied().unwrap_or(255),
+    ]))
+}
+
+/// Escape everything but unreserved characters for use in a url path.
+pub fn percent_encode(text: &str) -> String {
+    let mut encoded = String::new();
+    for byte in text.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    encoded
+}
+
+/// Escape text for use in xml, attribute values included.
+pub fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
 }
@@ -0,0 +1,98 @@
+use std::sync::Arc;
+
+use image::RgbaImage;
+use resvg::{tiny_skia, usvg};
+
+use crate::utils;
+
+// svg glyphs larger than this on either side are refused
+pub const MAX_VECTOR_SIZE: u32 = 4096;
+
+/// A glyph drawn from an svg document.
+#[derive(Debug, Clone)]
+pub struct VectorImage {
+    tree: Arc<usvg::Tree>,
+}
+
+/// Whether the bytes look like an svg document rather than a raster image.
+pub fn is_svg(bytes: &[u8]) -> bool {
+    let text = match std::str::from_utf8(&bytes[..bytes.len().min(1024)]) {
+        Ok(text) => text,
+        // cut in the middle of a character
+        Err(e) => std::str::from_utf8(&bytes[..e.valid_up_to()]).unwrap_or_default(),
+    };
+    let text = text.trim_start_matches('\u{feff}').trim_start();
+    text.starts_with('<') && text.contains("<svg")
+}
+
+impl VectorImage {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Box<dyn std::error::Error>> {
+        Self::parse(bytes, usvg::Options::default())
+    }
+
+    /// An svg document whose text is set in the given fonts, turned into
+    /// paths right away.
+    pub fn with_fonts(
+        bytes: &[u8],
+        fontdb: Arc<usvg::fontdb::Database>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let options = usvg::Options {
+            fontdb,
+            ..Default::default()
+        };
+        Self::parse(bytes, options)
+    }
+
+    fn parse(bytes: &[u8], mut options: usvg::Options) -> Result<Self, Box<dyn std::error::Error>> {
+        // glyphs must not reach for files on the server
+        options.image_href_resolver.resolve_string = Box::new(|_, _| None);
+        let tree = usvg::Tree::from_data(bytes, &options)?;
+
+        let size = tree.size();
+        if size.width() > MAX_VECTOR_SIZE as f32 || size.height() > MAX_VECTOR_SIZE as f32 {
+            return Err(Box::new(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("svg is larger than {MAX_VECTOR_SIZE}x{MAX_VECTOR_SIZE}"),
+            )));
+        }
+
+        Ok(VectorImage {
+            tree: Arc::new(tree),
+        })
+    }
+
+    pub fn width(&self) -> u32 {
+        (self.tree.size().width().ceil() as u32).max(1)
+    }
+
+    pub fn height(&self) -> u32 {
+        (self.tree.size().height().ceil() as u32).max(1)
+    }
+
+    /// Rasterize at `scale` times the size of the document.
+    pub fn rasterize(&self, scale: f32) -> Option<RgbaImage> {
+        let width = ((self.width() as f32 * scale).round() as u32).max(1);
+        let height = ((self.height() as f32 * scale).round() as u32).max(1);
+
+        let mut pixmap = tiny_skia::Pixmap::new(width, height)?;
+        resvg::render(
+            &self.tree,
+            tiny_skia::Transform::from_scale(scale, scale),
+            &mut pixmap.as_mut(),
+        );
+
+        Some(utils::pixmap_to_rgba(&pixmap))
+    }
+
+    /// The document as `<svg>` cleaned up by usvg, scripts and external
+    /// references dropped. Ids inside it start with `id_prefix`, so
+    /// several glyphs can share one document.
+    pub fn to_svg(&self, id_prefix: &str) -> String {
+        let options = usvg::WriteOptions {
+            id_prefix: Some(id_prefix.to_string()),
+            indent: usvg::Indent::None,
+            ..Default::default()
+        };
+        self.tree.to_string(&options)
+    }
+}
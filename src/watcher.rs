@@ -0,0 +1,37 @@
+use std::{path::Path, time::Duration};
+
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use tokio::sync::mpsc;
+
+// wait for a burst of changes, like copying a theme folder, to settle
+const SETTLE_TIME: Duration = Duration::from_millis(500);
+
+/// Watch a directory and everything below it, the receiver gets a message
+/// for every change. Changes stop being reported once the watcher drops.
+pub fn watch(dir: &str) -> notify::Result<(RecommendedWatcher, mpsc::UnboundedReceiver<()>)> {
+    let (tx, rx) = mpsc::unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+        if let Ok(event) = event {
+            if !event.kind.is_access() {
+                let _ = tx.send(());
+            }
+        }
+    })?;
+    watcher.watch(Path::new(dir), RecursiveMode::Recursive)?;
+    Ok((watcher, rx))
+}
+
+/// Wait for the next change and swallow the ones that follow it closely,
+/// `false` once the watcher is gone.
+pub async fn changed(rx: &mut mpsc::UnboundedReceiver<()>) -> bool {
+    if rx.recv().await.is_none() {
+        return false;
+    }
+    loop {
+        match tokio::time::timeout(SETTLE_TIME, rx.recv()).await {
+            Ok(Some(())) => continue,
+            Ok(None) => return false,
+            Err(_) => return true,
+        }
+    }
+}